@@ -0,0 +1,172 @@
+//! **Please refer to [`constany_stage_one` document](https://docs.rs/constany_stage_one/0.1.0/constany_stage_one/).**
+//! This crate is a helper for `build.rs`, which runs stage one and then compiles the crate as stage two.
+//!
+//! Call it from the `main` function of `build.rs`:
+//! ```no_run
+//! constany_build::run();
+//! ```
+
+use std::path::{Path, PathBuf};
+
+/// Run stage one with the `stage_one` feature, and build the crate with the `stage_two` feature.
+pub fn run() {
+    run_with_features("stage_one", "stage_two");
+}
+
+/// Same as `run`, but with custom feature names for the two stages: eg. `run_with_features("const_gen_1", "const_gen_2")`.
+pub fn run_with_features(stage_one: &str, stage_two: &str) {
+    if let Ok(i) = std::env::var("NO_BUILD") {
+        if i == "true" {
+            return;
+        }
+    }
+    // Both stages keep the resources under the target triple, so that builds for other targets can run at the same time.
+    let target = std::env::var("TARGET").unwrap_or_default();
+    let output = std::process::Command::new(std::env::var("CARGO").unwrap())
+        .args(["run", "--release", "--features", stage_one])
+        .env("NO_BUILD", "true")
+        .env("CONSTANY_TARGET", &target)
+        .stdout(std::process::Stdio::inherit())
+        .output()
+        .unwrap();
+    // Cargo only shows the output of a build script when it fails, except for `cargo:warning`.
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        match line.strip_prefix("constany: warning: ") {
+            Some(i) => println!("cargo:warning={}", i),
+            None => eprintln!("{}", line),
+        }
+    }
+    // Stage two would only report the missing resources, while cargo shows what stage one printed when the build script fails.
+    if !output.status.success() {
+        eprintln!("constany: stage one failed with {}", output.status);
+        std::process::exit(1);
+    }
+    println!("cargo:rustc-env=CONSTANY_TARGET={}", target);
+    println!("cargo:rustc-cfg=feature=\"{}\"", stage_two);
+}
+
+/// Remove the resource files of the crate, so that the next build evaluates every function again, without removing the rest of `target` like `cargo clean`.
+///
+/// The files are looked for where stage one writes them: in `CONSTANY_RESOURCE_DIR` (or the directory of `CONSTANY_TEMP_RESOURCES`, or `target`), relative to the current directory, under `CONSTANY_TARGET` when it is set, and otherwise also in each of its subdirectories, where `run` keeps the files of each target.
+/// Only files named and laid out like a resource are removed: `<name>.<CONSTANY_RESOURCE_EXT>` starting with the header of a resource (or empty, when the function panicked), the `<name>.rs` of `include` next to it, and the temporary files of an interrupted stage one.
+/// Returns the removed files.
+pub fn clean() -> std::io::Result<Vec<PathBuf>> {
+    let dir = PathBuf::from(constany_common::resource_dir());
+    let mut removed = Vec::new();
+    if !dir.is_dir() {
+        return Ok(removed);
+    }
+    let mut dirs = vec![dir.clone()];
+    if std::env::var("CONSTANY_TARGET").map_or(true, |i| i.is_empty()) {
+        for i in std::fs::read_dir(&dir)? {
+            let i = i?.path();
+            if i.is_dir() {
+                dirs.push(i);
+            }
+        }
+    }
+    let extension = format!(".{}", constany_common::resource_extension());
+    for dir in dirs {
+        for i in std::fs::read_dir(&dir)? {
+            let path = i?.path();
+            let file_name = match path.file_name().and_then(|i| i.to_str()) {
+                Some(i) => i.to_string(),
+                None => continue,
+            };
+            if let Some(name) = file_name.strip_suffix(&extension) {
+                if !is_function_name(name) || !is_resource(&path) {
+                    continue;
+                }
+                let included = dir.join(format!("{}.rs", name));
+                if included.is_file() {
+                    std::fs::remove_file(&included)?;
+                    removed.push(included);
+                }
+            } else {
+                // Stage one writes `<name>.<extension>.<process id>.tmp` before renaming it.
+                let temporary = file_name
+                    .strip_suffix(".tmp")
+                    .and_then(|i| i.rsplit_once('.'))
+                    .and_then(|(i, j)| Some((i.strip_suffix(&extension)?, j)));
+                match temporary {
+                    Some((name, id))
+                        if is_function_name(name)
+                            && !id.is_empty()
+                            && id.bytes().all(|i| i.is_ascii_digit()) => {}
+                    _ => continue,
+                }
+            }
+            if path.is_file() {
+                std::fs::remove_file(&path)?;
+                removed.push(path);
+            }
+        }
+    }
+    Ok(removed)
+}
+
+/// Whether a file name can be the one of a resource: the name of a function, or of an expression of `const_expr`.
+fn is_function_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|i: char| i.is_ascii_digit())
+        && name.chars().all(|i| i.is_alphanumeric() || i == '_')
+}
+
+/// Whether a file is empty, or starts with the header of a resource.
+fn is_resource(path: &Path) -> bool {
+    use std::io::Read;
+    let mut header = Vec::new();
+    match std::fs::File::open(path).and_then(|i| {
+        i.take(constany_common::HEADER_LEN as u64)
+            .read_to_end(&mut header)
+    }) {
+        Ok(0) => true,
+        Ok(i) => {
+            i == constany_common::HEADER_LEN
+                && constany_common::Method::from_byte(header[0]).is_some()
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn clean() {
+        let dir = std::env::temp_dir().join(format!("constany_clean_{}", std::process::id()));
+        let target = dir.join("x86_64-unknown-linux-gnu");
+        std::fs::create_dir_all(&target).unwrap();
+        let mut resource = vec![0u8];
+        resource.extend_from_slice(&[7; 16]);
+        resource.extend_from_slice(b"42");
+        let owned = [
+            (dir.join("sum.res"), resource.clone()),
+            (dir.join("sum.rs"), b"42".to_vec()),
+            (dir.join("panicked.res"), Vec::new()),
+            (dir.join("expr_00000000000000ff.res"), resource.clone()),
+            (dir.join("sum.res.1234.tmp"), resource.clone()),
+            (target.join("sum.res"), resource.clone()),
+        ];
+        let kept = [
+            (dir.join("notes.res"), b"not a resource".to_vec()),
+            (dir.join("short.res"), vec![0, 1, 2]),
+            (dir.join("other.rs"), b"fn main() {}".to_vec()),
+            (dir.join("Cargo.lock"), resource.clone()),
+            (dir.join("sum.res.tmp"), resource.clone()),
+            (dir.join("my file.res"), resource),
+        ];
+        for (path, content) in owned.iter().chain(&kept) {
+            std::fs::write(path, content).unwrap();
+        }
+        std::env::set_var("CONSTANY_RESOURCE_DIR", &dir);
+        let mut removed = super::clean().unwrap();
+        removed.sort();
+        let mut expected = owned.iter().map(|(i, _)| i.clone()).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(removed, expected);
+        for (path, _) in &kept {
+            assert!(path.exists(), "{}", path.display());
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,1284 @@
+//! **Please refer to [`constany_stage_one` document](https://docs.rs/constany_stage_one/0.1.0/constany_stage_one/).**
+//! This crate holds the logic shared by `constany_stage_one` and `constany_stage_two`, so that both stages always agree on how a function is recorded and rebuilt.
+
+pub mod rebuild;
+
+/// Solution picked for a type that constany cannot hard-code by itself.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    /// Record the value as raw memory and transmute it back.
+    Memop,
+    /// Record the value with `Debug` and rebuild it through its public fields.
+    DebugPub,
+    /// Record the value with `serde_json` and deserialize it back.
+    Serde,
+    /// Record the value with `serde_json`, and deserialize it once into a `static`.
+    LazyJson,
+    /// Record the bytes of the value (`AsRef<[u8]>`), hard-code them as base64, and rebuild the value with `from_bytes` on every call.
+    Base64,
+    /// Record the keys of a `HashSet` or `BTreeSet` of `&'static str`, and rebuild it as a `phf::Set` in stage two.
+    Phf,
+}
+
+/// Items generated by stage two, picked with `emit = "..."`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Emit {
+    /// Only the function.
+    #[default]
+    Fn,
+    /// A `const` named after the function in upper case, and the function returning it.
+    ConstItem,
+    /// A `static` named after the function in upper case, and the function returning it.
+    StaticItem,
+}
+
+/// Flags given to `const_fn`: eg. `#[const_fn(memop, force_const)]`.
+///
+/// Both stages read the same attribute, so unknown flags are rejected instead of being silently ignored by one of them.
+#[derive(Clone, Default)]
+pub struct Options {
+    /// How to record a type that is not supported out of the box.
+    pub mode: Option<Mode>,
+    /// Hard-code the value as a constant outside the function.
+    pub force_const: bool,
+    /// Evaluate the function in stage one with an empty environment, apart from its seed in `CONSTANY_SEED`.
+    pub pure: bool,
+    /// Only hard-code the length of the returned collection.
+    pub len_only: bool,
+    /// Write the value to a Rust file, which stage two pastes back with `include!`.
+    pub include: bool,
+    /// The supported type hidden behind the return type, eg. an alias: eg. `as = "u32"`.
+    pub as_type: Option<syn::Type>,
+    /// Return a reference to a `static` holding the value, instead of the value itself.
+    pub static_ref: bool,
+    /// Return the `String` of the function as `&'static str`, which stage two hard-codes as a literal.
+    pub intern: bool,
+    /// Record the value with `{:#?}` instead of `{:?}`, one field per line.
+    pub debug_pretty: bool,
+    /// Record each element of the returned array on its own with `serde`, and rebuild the array element by element.
+    pub array_elementwise: bool,
+    /// Also hard-code the length of the returned `&'static [T]` as a constant, eg. `NAME_LEN`.
+    pub with_len: bool,
+    /// Items generated by stage two: eg. `emit = "const_item"`.
+    pub emit: Emit,
+    /// Refuse to hard-code values larger than this: eg. `max_bytes = 1024`. Defaults to `CONSTANY_MAX_BYTES`.
+    pub max_bytes: Option<usize>,
+    /// Files read by the function, relative to `Cargo.toml`: eg. `depends_on = "config.toml"`.
+    pub depends_on: Vec<String>,
+    /// Environment variables read by the function: eg. `depends_env = "PROFILE"`.
+    pub depends_env: Vec<String>,
+    /// Features of the crate changing the value of the function: eg. `depends_feature = "metric"`.
+    pub depends_feature: Vec<String>,
+    /// File the record of the function is compared with in stage two, relative to `Cargo.toml`: eg. `golden = "golden/table.txt"`.
+    pub golden: Option<String>,
+    /// File holding the value as an expression, const for types built in a const, relative to `Cargo.toml`: eg. `manual = "src/value.rs"`. Stage one does not evaluate the function.
+    pub manual: Option<String>,
+    /// Function rebuilding a `base64` value from its bytes: eg. `from_bytes = "Blob::from_bytes"`.
+    pub from_bytes: Option<syn::Path>,
+    /// Names of the constants stage two adds for the elements of the returned tuple: eg. `split(TABLE, CHECKSUM)`.
+    pub split: Vec<syn::Ident>,
+    /// Name of the function stage one generates for `main_fn` to call, instead of `_{name}_wrapper_fn`: eg. `wrapper = "evaluate_table"`.
+    pub wrapper: Option<syn::Ident>,
+}
+
+impl Options {
+    pub fn from_args(args: &[syn::NestedMeta]) -> syn::Result<Self> {
+        let mut options = Options::default();
+        for i in args {
+            let unknown = || syn::Error::new_spanned(i, "Unknown constany flag");
+            match i {
+                syn::NestedMeta::Meta(syn::Meta::Path(j)) => {
+                    let mode = match j.get_ident().map(|k| k.to_string()).as_deref() {
+                        Some("memop") => Mode::Memop,
+                        Some("debug_pub") => Mode::DebugPub,
+                        Some("serde") => Mode::Serde,
+                        Some("lazy_json") => Mode::LazyJson,
+                        Some("base64") => Mode::Base64,
+                        Some("phf") => Mode::Phf,
+                        Some("force_const") => {
+                            options.force_const = true;
+                            continue;
+                        }
+                        Some("pure") => {
+                            options.pure = true;
+                            continue;
+                        }
+                        Some("len_only") => {
+                            options.len_only = true;
+                            continue;
+                        }
+                        Some("include") => {
+                            options.include = true;
+                            continue;
+                        }
+                        Some("static_ref") => {
+                            options.static_ref = true;
+                            continue;
+                        }
+                        Some("intern") => {
+                            options.intern = true;
+                            continue;
+                        }
+                        Some("debug_pretty") => {
+                            options.debug_pretty = true;
+                            continue;
+                        }
+                        Some("array_elementwise") => {
+                            options.array_elementwise = true;
+                            continue;
+                        }
+                        Some("with_len") => {
+                            options.with_len = true;
+                            continue;
+                        }
+                        _ => return Err(unknown()),
+                    };
+                    if options.mode.replace(mode).is_some() {
+                        return Err(syn::Error::new_spanned(
+                            i,
+                            "Only one of `memop`, `debug_pub`, `serde`, `lazy_json`, `base64` and `phf` can be used",
+                        ));
+                    }
+                }
+                syn::NestedMeta::Meta(syn::Meta::NameValue(j)) => {
+                    match (j.path.get_ident().map(|k| k.to_string()).as_deref(), &j.lit) {
+                        (Some("max_bytes"), syn::Lit::Int(k)) => {
+                            options.max_bytes = Some(k.base10_parse()?)
+                        }
+                        (Some("depends_on"), syn::Lit::Str(k)) => {
+                            options.depends_on.push(k.value())
+                        }
+                        (Some("depends_env"), syn::Lit::Str(k)) => {
+                            options.depends_env.push(k.value())
+                        }
+                        (Some("depends_feature"), syn::Lit::Str(k)) => {
+                            options.depends_feature.push(k.value())
+                        }
+                        (Some("manual"), syn::Lit::Str(k)) => options.manual = Some(k.value()),
+                        (Some("golden"), syn::Lit::Str(k)) => options.golden = Some(k.value()),
+                        (Some("wrapper"), syn::Lit::Str(k)) => {
+                            options.wrapper = Some(k.parse().map_err(|_| {
+                                syn::Error::new_spanned(
+                                    k,
+                                    "`wrapper` needs the name of a function: eg. `wrapper = \"evaluate_table\"`",
+                                )
+                            })?)
+                        }
+                        (Some("as"), syn::Lit::Str(k)) => options.as_type = Some(k.parse()?),
+                        (Some("from_bytes"), syn::Lit::Str(k)) => {
+                            options.from_bytes = Some(k.parse()?)
+                        }
+                        (Some("emit"), syn::Lit::Str(k)) => {
+                            options.emit = match k.value().as_str() {
+                                "fn" => Emit::Fn,
+                                "const_item" => Emit::ConstItem,
+                                "static_item" => Emit::StaticItem,
+                                _ => {
+                                    return Err(syn::Error::new_spanned(
+                                        k,
+                                        "Expected `fn`, `const_item` or `static_item`",
+                                    ))
+                                }
+                            }
+                        }
+                        _ => return Err(unknown()),
+                    }
+                }
+                syn::NestedMeta::Meta(syn::Meta::List(j)) if j.path.is_ident("split") => {
+                    for k in &j.nested {
+                        match k {
+                            syn::NestedMeta::Meta(syn::Meta::Path(l))
+                                if l.get_ident().is_some() =>
+                            {
+                                options.split.push(l.get_ident().unwrap().clone())
+                            }
+                            _ => {
+                                return Err(syn::Error::new_spanned(
+                                    k,
+                                    "Expected the name of a constant: eg. `split(TABLE, CHECKSUM)`",
+                                ))
+                            }
+                        }
+                    }
+                }
+                _ => return Err(unknown()),
+            }
+        }
+        if options.manual.is_some() {
+            let conflict = [
+                ("a mode", options.mode.is_some()),
+                ("`pure`", options.pure),
+                ("`len_only`", options.len_only),
+                ("`include`", options.include),
+                ("`max_bytes`", options.max_bytes.is_some()),
+                ("`depends_on`", !options.depends_on.is_empty()),
+                ("`depends_env`", !options.depends_env.is_empty()),
+                ("`depends_feature`", !options.depends_feature.is_empty()),
+                ("`static_ref`", options.static_ref),
+                ("`as`", options.as_type.is_some()),
+                ("`golden`", options.golden.is_some()),
+                ("`from_bytes`", options.from_bytes.is_some()),
+                ("`with_len`", options.with_len),
+            ]
+            .iter()
+            .find(|i| i.1)
+            .map(|i| i.0);
+            if let Some(flag) = conflict {
+                return Err(syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    format!(
+                        "`manual` cannot be used with {}, because stage one does not evaluate the function",
+                        flag
+                    ),
+                ));
+            }
+            return Ok(options);
+        }
+        match (options.mode, &options.from_bytes) {
+            (Some(Mode::Base64), None) => {
+                return Err(syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    "`base64` needs `from_bytes`, the function rebuilding the value from its bytes: eg. `from_bytes = \"Blob::from_bytes\"`",
+                ))
+            }
+            (mode, Some(i)) if mode != Some(Mode::Base64) => {
+                return Err(syn::Error::new_spanned(
+                    i,
+                    "`from_bytes` can only be used with `base64`",
+                ))
+            }
+            _ => {}
+        }
+        if options.max_bytes.is_none() {
+            if let Ok(i) = std::env::var("CONSTANY_MAX_BYTES") {
+                options.max_bytes = Some(i.parse().map_err(|_| {
+                    syn::Error::new(
+                        proc_macro2::Span::call_site(),
+                        "`CONSTANY_MAX_BYTES` should be a number",
+                    )
+                })?);
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Change the signature of the function as requested by the options, so that both stages agree on it.
+///
+/// For `len_only`, `fn name() -> T` becomes a function returning the length of its result as `usize`.
+/// For `lazy_json`, `phf` and `static_ref`, it becomes a function returning `&'static T`, evaluated once. Stage two changes the type again for `phf`.
+/// For `intern`, `fn name() -> String` becomes a function returning `&'static str`, evaluated once.
+/// The original function is kept inside under the same name.
+pub fn rewrite(item: syn::ItemFn, options: &Options) -> syn::Result<syn::ItemFn> {
+    let output_type = match &item.sig.output {
+        syn::ReturnType::Type(_, i) => i.clone(),
+        syn::ReturnType::Default => return Ok(item),
+    };
+    if options.intern
+        && !generic_type(&output_type).is_some_and(|i| i.0 == "String" && i.1.is_empty())
+    {
+        return Err(syn::Error::new_spanned(
+            output_type,
+            "`intern` needs a function returning `String`",
+        ));
+    }
+    let name = item.sig.ident.clone();
+    let names = parameter_names(&item.sig);
+    let mut inner = item.clone();
+    inner.attrs.clear();
+    inner.vis = syn::Visibility::Inherited;
+    let types = parameter_types(&inner.sig);
+    let mut outer = item;
+    let evaluated_once = matches!(options.mode, Some(Mode::LazyJson) | Some(Mode::Phf))
+        || options.static_ref
+        || options.intern;
+    if options.len_only || evaluated_once {
+        outer.sig.inputs = syn::parse_quote! { #(#names: #types),* };
+    }
+    if options.len_only {
+        outer.sig.output = syn::parse_quote! { -> usize };
+        outer.block = syn::parse_quote! {
+            {
+                #inner
+                #name(#(#names),*).len()
+            }
+        };
+    } else if evaluated_once {
+        // The `String` is kept in the `static` and borrowed as `&'static str`.
+        outer.sig.output = if options.intern {
+            syn::parse_quote! { -> &'static str }
+        } else {
+            syn::parse_quote! { -> &'static #output_type }
+        };
+        outer.block = syn::parse_quote! {
+            {
+                #inner
+                static VALUE: std::sync::OnceLock<#output_type> = std::sync::OnceLock::new();
+                VALUE.get_or_init(|| #name(#(#names),*))
+            }
+        };
+    }
+    Ok(outer)
+}
+
+/// Check that a function can become constant, and return its output type.
+pub fn output_type(sig: &syn::Signature) -> syn::Result<&syn::Type> {
+    // Checked before generics, which are often only the lifetime of the reference.
+    if let syn::ReturnType::Type(_, i) = &sig.output {
+        if borrows(quote::quote!(#i)) {
+            return Err(syn::Error::new_spanned(
+                i,
+                format!(
+                    "`{}` borrows from something that only lives while the function runs, so it cannot be hard-coded. Only `'static` references can become constant: please return `&'static str`, `&'static [T]` or an owned value",
+                    quote::quote!(#i)
+                ),
+            ));
+        }
+    }
+    if !sig.generics.params.is_empty() || sig.generics.where_clause.is_some() {
+        return Err(syn::Error::new_spanned(
+            &sig.generics,
+            "Generic fn cannot become constant, because stage one cannot know which types to evaluate it with. Please wrap it in a non-generic fn for every type you need",
+        ));
+    }
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(syn::Error::new_spanned(
+            asyncness,
+            "Async fn cannot become constant, because stage one cannot wait for its future. Please make it a normal fn",
+        ));
+    }
+    if let Some(receiver) = sig.receiver() {
+        return Err(syn::Error::new_spanned(
+            receiver,
+            "Method cannot become constant. Please move it out of the `impl` block",
+        ));
+    }
+    let output_type = match &sig.output {
+        syn::ReturnType::Default => {
+            return Err(syn::Error::new_spanned(
+                sig,
+                "Fn with `()` output should not become constant",
+            ))
+        }
+        syn::ReturnType::Type(_, i) => i,
+    };
+    if contains_ident(quote::quote!(#output_type), "impl") {
+        return Err(syn::Error::new_spanned(
+            output_type,
+            "Fn returning `impl Trait` cannot become constant, because the macro cannot see the concrete type. Please name the concrete type in the return type",
+        ));
+    }
+    if is_bare_str(output_type) {
+        return Err(syn::Error::new_spanned(
+            output_type,
+            "Fn cannot return unsized `str`. Please return `&'static str` instead",
+        ));
+    }
+    Ok(output_type)
+}
+
+/// Names for the parameters of a function, so that the generated code can pass them on.
+pub fn parameter_names(sig: &syn::Signature) -> Vec<syn::Ident> {
+    (0..parameter_types(sig).len())
+        .map(|i| quote::format_ident!("arg{}", i))
+        .collect()
+}
+
+/// Types of the parameters of a function. Receivers are rejected by [`output_type`].
+pub fn parameter_types(sig: &syn::Signature) -> Vec<&syn::Type> {
+    sig.inputs
+        .iter()
+        .filter_map(|i| match i {
+            syn::FnArg::Typed(i) => Some(&*i.ty),
+            syn::FnArg::Receiver(_) => None,
+        })
+        .collect()
+}
+
+/// Whether a parameter of the type never needs to be dropped, which a `const fn` cannot do: a reference, a pointer or a primitive type.
+pub fn drops_nothing(input: &syn::Type) -> bool {
+    match input {
+        syn::Type::Reference(_)
+        | syn::Type::Ptr(_)
+        | syn::Type::BareFn(_)
+        | syn::Type::Never(_) => true,
+        syn::Type::Group(syn::TypeGroup { elem, .. })
+        | syn::Type::Paren(syn::TypeParen { elem, .. }) => drops_nothing(elem),
+        _ => is_primitive_type(input),
+    }
+}
+
+/// The type of the recorded value: the type given with `as`, `T` for a `static_ref` function returning `&'static T`, or the output type.
+pub fn value_type<'a>(output_type: &'a syn::Type, options: &'a Options) -> &'a syn::Type {
+    match output_type {
+        _ if options.as_type.is_some() => options.as_type.as_ref().unwrap(),
+        syn::Type::Reference(i) if options.static_ref || options.mode == Some(Mode::Phf) => &i.elem,
+        _ => output_type,
+    }
+}
+
+/// Check the options that only work with some return types.
+pub fn check_options(output_type: &syn::Type, options: &Options) -> syn::Result<()> {
+    if let Some(as_type) = &options.as_type {
+        if options.len_only || options.mode.is_some() {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "`as` cannot be used with `len_only` or a mode",
+            ));
+        }
+        if Method::of(as_type, None).is_err() {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!(
+                    "`as` needs a supported type: {}",
+                    SUPPORTED_TYPES.join(", ")
+                ),
+            ));
+        }
+    }
+    if options.mode == Some(Mode::Phf) {
+        let keys = match generic_type(output_type) {
+            Some((ident, arguments)) if ident == "HashSet" || ident == "BTreeSet" => {
+                matches!(arguments.as_slice(), [syn::Type::Reference(i)] if is_static_str(i))
+            }
+            _ => false,
+        };
+        if !keys || options.len_only {
+            return Err(syn::Error::new_spanned(
+                output_type,
+                "`phf` needs a function returning `HashSet<&'static str>` or `BTreeSet<&'static str>`, and cannot be used with `len_only`",
+            ));
+        }
+    }
+    if options.array_elementwise
+        && (options.mode != Some(Mode::Serde)
+            || options.len_only
+            || !matches!(output_type, syn::Type::Array(_)))
+    {
+        return Err(syn::Error::new_spanned(
+            output_type,
+            "`array_elementwise` needs `serde` and a function returning an array `[T; N]`",
+        ));
+    }
+    if options.with_len
+        && (options.mode.is_some() || options.len_only || static_slice(output_type).is_none())
+    {
+        return Err(syn::Error::new_spanned(
+            output_type,
+            "`with_len` needs a function returning `&'static [T]` of a primitive `T`, and cannot be used with `len_only` or a mode",
+        ));
+    }
+    if options.debug_pretty
+        && matches!(
+            options.mode,
+            Some(Mode::Memop) | Some(Mode::Serde) | Some(Mode::LazyJson) | Some(Mode::Base64)
+        )
+    {
+        return Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`debug_pretty` only changes values recorded with `Debug`, so it cannot be used with `memop`, `serde`, `lazy_json` or `base64`",
+        ));
+    }
+    if options.intern
+        && (options.len_only
+            || options.mode == Some(Mode::LazyJson)
+            || options.static_ref
+            || options.as_type.is_some())
+    {
+        return Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "`intern` cannot be used with `len_only`, `lazy_json`, `static_ref` or `as`",
+        ));
+    }
+    if !options.split.is_empty() {
+        if options.len_only
+            || options.mode.is_some()
+            || options.static_ref
+            || options.intern
+            || options.emit != Emit::Fn
+        {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "`split` cannot be used with `len_only`, a mode, `static_ref`, `intern` or `emit`",
+            ));
+        }
+        match output_type {
+            syn::Type::Tuple(i) if i.elems.len() == options.split.len() => {
+                if let Some(j) = i.elems.iter().find(|j| !rebuild::is_const(j)) {
+                    return Err(syn::Error::new_spanned(
+                        j,
+                        "`split` needs values that can be built in a const: a primitive type, `&'static str`, `&'static [T]`, or an `Option` or `Result` of them",
+                    ));
+                }
+            }
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    output_type,
+                    format!(
+                        "`split` needs a tuple with as many elements as names: {} here",
+                        options.split.len()
+                    ),
+                ))
+            }
+        }
+    }
+    if options.include && !is_primitive_type(output_type) {
+        return Err(syn::Error::new_spanned(
+            output_type,
+            "`include` only supports primitive types, `&'static str`, and arrays or tuples of them",
+        ));
+    }
+    let constant = rebuild::is_const(output_type)
+        || (options.mode == Some(Mode::DebugPub)
+            && !rebuild::is_rebuildable(output_type)
+            && owned_slice(output_type).is_none()
+            && !is_static_cstr(output_type));
+    if options.emit != Emit::Fn && !constant && options.manual.is_none() {
+        return Err(syn::Error::new_spanned(
+            output_type,
+            "`const_item` and `static_item` need a value that can be built in a const: a primitive type, `&'static str`, `&'static [T]`, an `Option` or `Result` of them, or a `debug_pub` structure",
+        ));
+    }
+    if options.static_ref {
+        if options.len_only || options.mode == Some(Mode::LazyJson) || options.emit != Emit::Fn {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "`static_ref` cannot be used with `len_only`, `lazy_json` or `emit`",
+            ));
+        }
+        if !constant {
+            return Err(syn::Error::new_spanned(
+                output_type,
+                "`static_ref` needs a value that can be built in a const: a primitive type, `&'static str`, `&'static [T]`, an `Option` or `Result` of them, or a `debug_pub` structure",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the tokens of a type contain a lifetime other than `'static`, or a reference with an elided lifetime.
+fn borrows(tokens: proc_macro2::TokenStream) -> bool {
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(i) = tokens.next() {
+        let borrowed = match (&i, tokens.peek()) {
+            (proc_macro2::TokenTree::Punct(j), Some(proc_macro2::TokenTree::Ident(k)))
+                if j.as_char() == '\'' =>
+            {
+                k != "static"
+            }
+            (proc_macro2::TokenTree::Punct(j), k) if j.as_char() == '&' => {
+                !matches!(k, Some(proc_macro2::TokenTree::Punct(l)) if l.as_char() == '\'')
+            }
+            (proc_macro2::TokenTree::Group(j), _) => borrows(j.stream()),
+            _ => false,
+        };
+        if borrowed {
+            return true;
+        }
+    }
+    false
+}
+
+/// Rewrite the relative paths in tokens moved into a child module so they still resolve from the parent: a leading `self::` becomes `super::`, and a leading `super::` gains another one.
+pub fn reparent(tokens: proc_macro2::TokenStream) -> proc_macro2::TokenStream {
+    let mut result = Vec::new();
+    // Whether the previous tokens are `::`, in which case an identifier is not the first segment of a path.
+    let mut after_colons = false;
+    let mut last_colon = false;
+    for i in tokens {
+        let mut next_after_colons = false;
+        let mut next_last_colon = false;
+        match i {
+            proc_macro2::TokenTree::Ident(j) if !after_colons && (j == "self" || j == "super") => {
+                let span = j.span();
+                result.push(proc_macro2::TokenTree::Ident(proc_macro2::Ident::new(
+                    "super", span,
+                )));
+                if j == "super" {
+                    result.extend(quote::quote_spanned!(span=> ::super));
+                }
+            }
+            proc_macro2::TokenTree::Group(j) => {
+                let mut group = proc_macro2::Group::new(j.delimiter(), reparent(j.stream()));
+                group.set_span(j.span());
+                result.push(proc_macro2::TokenTree::Group(group));
+            }
+            proc_macro2::TokenTree::Punct(j) => {
+                if j.as_char() == ':' {
+                    next_after_colons = last_colon;
+                    next_last_colon = j.spacing() == proc_macro2::Spacing::Joint;
+                }
+                result.push(proc_macro2::TokenTree::Punct(j));
+            }
+            j => result.push(j),
+        }
+        after_colons = next_after_colons;
+        last_colon = next_last_colon;
+    }
+    result.into_iter().collect()
+}
+
+/// Whether the tokens contain the identifier, eg. `impl`, which can only start an opaque type such as `impl Display` in a type.
+fn contains_ident(tokens: proc_macro2::TokenStream, ident: &str) -> bool {
+    tokens.into_iter().any(|i| match i {
+        proc_macro2::TokenTree::Ident(j) => j == ident,
+        proc_macro2::TokenTree::Group(j) => contains_ident(j.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Names in the body of a function that a `const fn` cannot use: `for` loops, allocations, and common iterator, string and I/O methods.
+const NON_CONST_IDENTS: &[&str] = &[
+    "for",
+    "Vec",
+    "String",
+    "Box",
+    "Rc",
+    "Arc",
+    "Owned",
+    "HashMap",
+    "BTreeMap",
+    "BTreeSet",
+    "iter",
+    "into_iter",
+    "next",
+    "collect",
+    "map",
+    "filter",
+    "fold",
+    "find",
+    "any",
+    "all",
+    "count",
+    "sum",
+    "product",
+    "max",
+    "min",
+    "rev",
+    "zip",
+    "enumerate",
+    "take",
+    "skip",
+    "step_by",
+    "chars",
+    "bytes",
+    "lines",
+    "split",
+    "join",
+    "repeat",
+    "parse",
+    "push",
+    "insert",
+    "extend",
+    "sort",
+    "to_string",
+    "to_owned",
+    "to_vec",
+    "clone",
+    "fs",
+    "env",
+    "io",
+    "thread",
+];
+
+/// Macros that a `const fn` can use.
+const CONST_MACROS: &[&str] = &[
+    "panic",
+    "assert",
+    "debug_assert",
+    "unreachable",
+    "matches",
+    "concat",
+    "stringify",
+    "env",
+    "option_env",
+    "include_str",
+    "include_bytes",
+    "line",
+    "column",
+    "file",
+    "module_path",
+];
+
+/// Whether the function looks like it could be a `const fn` already: it returns a value that can be built in a const, and its body uses none of the constructs a `const fn` cannot.
+///
+/// This is only a guess pointing to a simpler solution. `while` and `loop` are allowed in a `const fn`, but most methods of the standard library are not, and they are not all listed.
+pub fn looks_const(item: &syn::ItemFn) -> bool {
+    fn tokens_look_const(tokens: proc_macro2::TokenStream) -> bool {
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(i) = tokens.next() {
+            let allowed = match &i {
+                proc_macro2::TokenTree::Ident(j) => match tokens.peek() {
+                    Some(proc_macro2::TokenTree::Punct(k)) if k.as_char() == '!' => {
+                        CONST_MACROS.iter().any(|k| j == k)
+                    }
+                    _ => !NON_CONST_IDENTS.iter().any(|k| j == k),
+                },
+                proc_macro2::TokenTree::Group(j) => tokens_look_const(j.stream()),
+                _ => true,
+            };
+            if !allowed {
+                return false;
+            }
+        }
+        true
+    }
+    match &item.sig.output {
+        syn::ReturnType::Type(_, i) if rebuild::is_const(i) => {}
+        _ => return false,
+    }
+    let block = &item.block;
+    tokens_look_const(quote::quote!(#block))
+}
+
+/// Fail if the item still carries the attribute being expanded, which happens when stage one and stage two are both enabled.
+pub fn check_single_stage(item: &syn::ItemFn, attribute: &str) -> syn::Result<()> {
+    for i in &item.attrs {
+        if contains_ident(quote::quote!(#i), attribute) {
+            return Err(syn::Error::new_spanned(
+                i,
+                format!(
+                    "`{}` is applied by both stages. Please use `constany_blank` when both `stage_one` and `stage_two` are enabled",
+                    attribute
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Resolve a path relative to the `Cargo.toml` of the crate being compiled.
+pub fn manifest_path(path: &str) -> std::path::PathBuf {
+    std::path::Path::new(&std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string()))
+        .join(path)
+}
+
+/// `path` as an argument of `include!` or `include_bytes!`, relative to `CARGO_MANIFEST_DIR` when it is inside the directory cargo builds from, so that the generated code does not depend on where the crate is checked out.
+pub fn include_arg(path: &std::path::Path) -> proc_macro2::TokenStream {
+    let root = std::env::current_dir().unwrap_or_default();
+    let manifest = manifest_path("");
+    match (path.strip_prefix(&root), manifest.strip_prefix(&root)) {
+        (Ok(path), Ok(manifest)) => {
+            let relative: String = manifest
+                .components()
+                .map(|_| "/..".to_string())
+                .chain(
+                    path.components()
+                        .map(|i| format!("/{}", i.as_os_str().to_string_lossy())),
+                )
+                .collect();
+            quote::quote! { concat!(env!("CARGO_MANIFEST_DIR"), #relative) }
+        }
+        _ => {
+            let path = path.to_string_lossy();
+            quote::quote! { #path }
+        }
+    }
+}
+
+/// Hash of the function source and every file and environment variable it depends on, which tells stage two whether the resource is stale.
+pub fn source_hash(item: &str, options: &Options) -> syn::Result<u64> {
+    let mut hashed = item.as_bytes().to_vec();
+    for i in &options.depends_on {
+        let content = std::fs::read(manifest_path(i)).map_err(|e| {
+            syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!("Unable to read `{}`: {}", i, e),
+            )
+        })?;
+        hashed.extend_from_slice(i.as_bytes());
+        hashed.extend_from_slice(&content);
+    }
+    for i in &options.depends_env {
+        hashed.extend_from_slice(i.as_bytes());
+        // An unset variable is not the same as an empty one.
+        match std::env::var_os(i) {
+            Some(value) => {
+                hashed.push(1);
+                hashed.extend_from_slice(value.to_string_lossy().as_bytes());
+            }
+            None => hashed.push(0),
+        }
+    }
+    Ok(seahash::hash(&hashed))
+}
+
+/// Expression mixing the enabled features of `depends_feature` into the hash of the function.
+///
+/// The macros cannot see which features are enabled, so the generated code checks them with `cfg!` and the hash is only known once it is compiled.
+pub fn feature_hash(options: &Options) -> proc_macro2::TokenStream {
+    let features = &options.depends_feature;
+    let hashes = features
+        .iter()
+        .map(|i| seahash::hash(format!("feature {}", i).as_bytes()));
+    quote::quote! { (0u64 #(^ if cfg!(feature = #features) { #hashes } else { 0 })*) }
+}
+
+/// Items making the compiler rebuild the crate when a file in `depends_on` or a variable in `depends_env` changes.
+pub fn track_dependencies(options: &Options) -> proc_macro2::TokenStream {
+    let paths = options
+        .depends_on
+        .iter()
+        .map(|i| include_arg(&manifest_path(i)));
+    let variables = &options.depends_env;
+    quote::quote! {
+        #(const _: &[u8] = include_bytes!(#paths);)*
+        #(const _: Option<&str> = option_env!(#variables);)*
+    }
+}
+
+/// Compare the record of `name` with its golden file, or write the file when `CONSTANY_BLESS=1` is set.
+pub fn check_golden(name: &str, record: &str, golden: &str) -> Result<(), String> {
+    let path = manifest_path(golden);
+    let bless = "Set `CONSTANY_BLESS=1` for stage two to write the current value to it";
+    if std::env::var("CONSTANY_BLESS").is_ok_and(|i| i == "1") {
+        return path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(&path, format!("{}\n", record)))
+            .map_err(|e| format!("Unable to write `{}`: {}", path.display(), e));
+    }
+    let expected = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Unable to read `{}` ({}). {}", path.display(), e, bless))?;
+    // Editors usually end files with a line break, which the record does not have.
+    let expected = expected
+        .strip_suffix('\n')
+        .map(|i| i.strip_suffix('\r').unwrap_or(i))
+        .unwrap_or(&expected);
+    if expected == record {
+        return Ok(());
+    }
+    let mut position = expected
+        .bytes()
+        .zip(record.bytes())
+        .take_while(|(i, j)| i == j)
+        .count();
+    while !expected.is_char_boundary(position) || !record.is_char_boundary(position) {
+        position -= 1;
+    }
+    let line = expected[..position].matches('\n').count() + 1;
+    let column = expected[..position]
+        .rsplit('\n')
+        .next()
+        .map_or(0, |i| i.chars().count())
+        + 1;
+    let excerpt = |text: &str| text[position..].chars().take(40).collect::<String>();
+    Err(format!(
+        "The value of `{}` differs from `{}` at line {}, column {}: expected {:?}, found {:?} ({} bytes expected, {} found). {}",
+        name,
+        golden,
+        line,
+        column,
+        excerpt(expected),
+        excerpt(record),
+        expected.len(),
+        record.len(),
+        bless
+    ))
+}
+
+/// Directory holding the resource files, relative to where stage one runs and stage two compiles.
+///
+/// Defaults to `target`, and can be changed with `CONSTANY_RESOURCE_DIR`.
+/// With `CONSTANY_TEMP_RESOURCES=1`, it defaults to a directory of the crate in [`temporary_resource_dir`] instead.
+/// When `CONSTANY_TARGET` is set (eg. to the target triple by `constany_build`), the files are kept in a subdirectory named after it, so that builds for different targets do not share them.
+pub fn resource_dir() -> String {
+    let dir = std::env::var("CONSTANY_RESOURCE_DIR").unwrap_or_else(|_| {
+        if std::env::var("CONSTANY_TEMP_RESOURCES").is_ok_and(|i| i == "1") {
+            temporary_resource_dir()
+        } else {
+            "target".to_string()
+        }
+    });
+    match std::env::var("CONSTANY_TARGET") {
+        Ok(i) if !i.is_empty() => format!("{}/{}", dir, i),
+        _ => dir,
+    }
+}
+
+/// Directory in memory (`/dev/shm` when it exists, the temporary directory otherwise) for the resources of the crate being compiled, named after a hash of its `Cargo.toml` directory.
+///
+/// Both stages need to see the same directory, so this only saves disk writes: the files are still read back by another process.
+pub fn temporary_resource_dir() -> String {
+    let shm = std::path::Path::new("/dev/shm");
+    let base = if shm.is_dir() {
+        shm.to_path_buf()
+    } else {
+        std::env::temp_dir()
+    };
+    let manifest = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
+    base.join("constany")
+        .join(format!("{:016x}", seahash::hash(manifest.as_bytes())))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Extension of the resource files.
+///
+/// Defaults to `res`, and can be changed with `CONSTANY_RESOURCE_EXT`.
+pub fn resource_extension() -> String {
+    match std::env::var("CONSTANY_RESOURCE_EXT") {
+        Ok(i) => i.trim_start_matches('.').to_string(),
+        Err(_) => "res".to_string(),
+    }
+}
+
+/// Length of the header of a resource file: the method, the hash of the function and the hash of its return type, before the value itself.
+pub const HEADER_LEN: usize = 17;
+
+/// First character of a record repeating the value of a function at the root of the crate: the record is then `=name`, a newline, and the value.
+pub const SHARED_PREFIX: char = '=';
+
+/// The function whose value a record repeats, if any, and the value itself.
+pub fn split_shared(record: &str) -> (Option<&str>, &str) {
+    match record
+        .strip_prefix(SHARED_PREFIX)
+        .and_then(|i| i.split_once('\n'))
+    {
+        Some((name, value)) => (Some(name), value),
+        None => (None, record),
+    }
+}
+
+/// Hash of the return type of a function, which stage two compares with the one recorded by stage one.
+///
+/// A changed return type also changes the hash of the function, but this tells the two apart, so that the error can say what happened.
+pub fn type_hash(output_type: &syn::Type) -> u64 {
+    seahash::hash(quote::quote!(#output_type).to_string().as_bytes())
+}
+
+/// Path of the resource file recording `name`.
+pub fn resource_path(name: &str) -> String {
+    format!("{}/{}.{}", resource_dir(), name, resource_extension())
+}
+
+/// Read the resource file recording `name`, with an error naming the file and the reason if it cannot be used.
+pub fn read_resource(name: &str) -> Result<Vec<u8>, String> {
+    let path = std::env::current_dir()
+        .unwrap_or_default()
+        .join(resource_path(name));
+    match std::fs::read(&path) {
+        Ok(i) if i.len() >= HEADER_LEN => Ok(i),
+        Ok(i) if i.is_empty() => Err(format!(
+            "`{}` is empty, because the function panicked when stage one evaluated it. Please fix the function and execute stage one again",
+            path.display()
+        )),
+        Ok(_) => Err(format!(
+            "`{}` is broken. Please execute stage one again",
+            path.display()
+        )),
+        Err(e) => Err(format!(
+            "Unable to read `{}` ({:?}: {}). Please make sure stage one has been executed before compiling the final product",
+            path.display(),
+            e.kind(),
+            e
+        )),
+    }
+}
+
+/// Path of the Rust file holding the value of `name`, for `include`.
+pub fn include_path(name: &str) -> String {
+    format!("{}/{}.rs", resource_dir(), name)
+}
+
+/// Name of the resource recording a `const_expr!` with the given hash.
+pub fn expr_resource_name(code_hash: u64) -> String {
+    format!("expr_{:016x}", code_hash)
+}
+
+/// Names of the functions stage one generates for `name`: the wrapper `main_fn` calls, and the one checking whether its resource is up to date.
+///
+/// `wrapper` is the name picked with `wrapper = "..."`, and the second function is named after it.
+pub fn wrapper_names(name: &str, wrapper: Option<&syn::Ident>) -> (syn::Ident, syn::Ident) {
+    let name = name.trim_start_matches("r#");
+    match wrapper {
+        Some(i) => (i.clone(), quote::format_ident!("{}_unchanged", i)),
+        None => (
+            quote::format_ident!("_{}_wrapper_fn", name),
+            quote::format_ident!("_{}_unchanged_fn", name),
+        ),
+    }
+}
+
+/// Whether `CONSTANY_STRICT=1` is set for the build.
+///
+/// In this mode, stage two reports a missing, broken or stale resource with where it expected it and how to regenerate it.
+pub fn strict() -> bool {
+    std::env::var("CONSTANY_STRICT").is_ok_and(|i| i == "1")
+}
+
+/// The message stage two reports when the resource of `name` cannot be used, with the steps to fix it in strict mode.
+pub fn resource_error(name: &str, problem: &str) -> String {
+    if !strict() {
+        return problem.to_string();
+    }
+    let path = std::env::current_dir()
+        .unwrap_or_default()
+        .join(resource_path(name));
+    format!(
+        "`{}` has no valid resource, and `CONSTANY_STRICT=1` forbids building it without one.\n  problem: {}\n  expected resource: {}\n  to regenerate it: run stage one (eg. `cargo run --features stage_one`, or `cargo build` with `constany_build`) with the same `CONSTANY_RESOURCE_DIR`, `CONSTANY_RESOURCE_EXT`, `CONSTANY_TARGET` and features, then build with stage two again",
+        name,
+        problem,
+        path.display()
+    )
+}
+
+/// Whether `CONSTANY_REPRODUCIBLE=1` is set for the build.
+///
+/// In this mode every function is treated as `pure`, `SystemTime` values are frozen, and stage one sorts the keys of JSON objects.
+pub fn reproducible() -> bool {
+    std::env::var("CONSTANY_REPRODUCIBLE").is_ok_and(|i| i == "1")
+}
+
+/// The time every `SystemTime` is recorded as in reproducible builds: `SOURCE_DATE_EPOCH`, or `UNIX_EPOCH` if it is not set.
+pub fn frozen_time() -> Option<u64> {
+    if reproducible() {
+        Some(
+            std::env::var("SOURCE_DATE_EPOCH")
+                .ok()
+                .and_then(|i| i.parse().ok())
+                .unwrap_or(0),
+        )
+    } else {
+        None
+    }
+}
+
+/// How the value of a constant function is recorded in stage one and rebuilt in stage two.
+///
+/// The discriminant is the first byte of the resource file.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Method {
+    /// The value is recorded with `Debug` and pasted back as a literal.
+    Debug = 0,
+    /// The value is recorded as raw memory and transmuted back.
+    Memop = 1,
+    /// The bytes of the value (`CStr::to_bytes_with_nul`, or a `Vec<u8>`) are recorded and pasted back as a byte string.
+    Bytes = 2,
+    /// The value is recorded as JSON and deserialized back.
+    Serde = 3,
+    /// The bytes of the value (`AsRef<[u8]>`) are recorded and pasted back as base64, which `from_bytes` rebuilds the value from.
+    Base64 = 4,
+}
+
+impl Method {
+    /// Read the method from the first byte of a resource file.
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Method::Debug),
+            1 => Some(Method::Memop),
+            2 => Some(Method::Bytes),
+            3 => Some(Method::Serde),
+            4 => Some(Method::Base64),
+            _ => None,
+        }
+    }
+    /// Pick the method for a function returning `output_type`.
+    ///
+    /// Supported types are always recorded the same way, regardless of the mode. Other types need a mode.
+    pub fn of(output_type: &syn::Type, mode: Option<Mode>) -> syn::Result<Self> {
+        // A byte string is much shorter than `vec![..]` of every byte, and faster to compile.
+        if is_byte_vec(output_type) {
+            return Ok(Method::Bytes);
+        } else if rebuild::is_rebuildable(output_type) || owned_slice(output_type).is_some() {
+            return Ok(Method::Debug);
+        } else if is_static_cstr(output_type) {
+            return Ok(Method::Bytes);
+        }
+        match mode {
+            Some(Mode::Memop) => Ok(Method::Memop),
+            Some(Mode::DebugPub) => Ok(Method::Debug),
+            Some(Mode::Serde) | Some(Mode::LazyJson) => Ok(Method::Serde),
+            Some(Mode::Base64) => Ok(Method::Base64),
+            Some(Mode::Phf) => Ok(Method::Debug),
+            None => {
+                // A bare name is often an alias of a supported type, which the macro cannot resolve.
+                let alias = match output_type {
+                    syn::Type::Path(i) => {
+                        i.qself.is_none()
+                            && i.path.segments.len() == 1
+                            && i.path.segments[0].arguments.is_empty()
+                    }
+                    _ => false,
+                };
+                let hint = if alias {
+                    format!(
+                        ". If `{}` is a type alias, give the real type with `as`: eg. `#[const_fn(as = \"u32\")]`",
+                        quote::quote!(#output_type)
+                    )
+                } else {
+                    String::new()
+                };
+                Err(syn::Error::new_spanned(
+                    output_type,
+                    format!(
+                        "`{}` cannot be hard-coded by default. If it implements `Serialize` and `Deserialize`, `#[const_fn(lazy_json)]` builds it once and returns `&'static` to it. Otherwise, please pick a solution with `#[const_fn(memop)]`, `#[const_fn(debug_pub)]`, `#[const_fn(serde)]` or `#[const_fn(base64, from_bytes = \"...\")]`, or return a supported type: {}{}",
+                        quote::quote!(#output_type),
+                        SUPPORTED_TYPES.join(", "),
+                        hint
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+/// Whether the type is `str` itself, which is unsized and cannot be returned.
+pub fn is_bare_str(input: &syn::Type) -> bool {
+    match input {
+        syn::Type::Path(i) => i.qself.is_none() && i.path.is_ident("str"),
+        syn::Type::Group(syn::TypeGroup { elem, .. })
+        | syn::Type::Paren(syn::TypeParen { elem, .. }) => is_bare_str(elem),
+        _ => false,
+    }
+}
+
+/// The referenced type of a shared `&'static` reference.
+fn static_reference(input: &syn::TypeReference) -> Option<&syn::Type> {
+    match &input.lifetime {
+        Some(i) if i.ident == "static" && input.mutability.is_none() => Some(&input.elem),
+        _ => None,
+    }
+}
+
+/// Whether the reference is `&'static str`, whose `Debug` output is a valid literal.
+fn is_static_str(input: &syn::TypeReference) -> bool {
+    static_reference(input).is_some_and(is_bare_str)
+}
+
+/// The slice type of `&'static [T]`, if `T` is primitive.
+pub fn static_slice(input: &syn::Type) -> Option<&syn::Type> {
+    match input {
+        syn::Type::Reference(i) => match static_reference(i) {
+            Some(syn::Type::Slice(j)) if is_primitive_type(&j.elem) => static_reference(i),
+            _ => None,
+        },
+        syn::Type::Group(syn::TypeGroup { elem, .. })
+        | syn::Type::Paren(syn::TypeParen { elem, .. }) => static_slice(elem),
+        _ => None,
+    }
+}
+
+/// Whether the type is `&'static CStr`.
+pub fn is_static_cstr(input: &syn::Type) -> bool {
+    let elem = match input {
+        syn::Type::Reference(i) => static_reference(i),
+        syn::Type::Group(syn::TypeGroup { elem, .. })
+        | syn::Type::Paren(syn::TypeParen { elem, .. }) => return is_static_cstr(elem),
+        _ => None,
+    };
+    match elem.and_then(generic_type) {
+        Some((ident, arguments)) => ident == "CStr" && arguments.is_empty(),
+        None => false,
+    }
+}
+
+/// Whether the type is `Vec<u8>`.
+pub fn is_byte_vec(input: &syn::Type) -> bool {
+    match generic_type(input) {
+        Some((ident, arguments)) if ident == "Vec" => matches!(
+            arguments.as_slice(),
+            [i] if matches!(generic_type(i), Some((j, k)) if j == "u8" && k.is_empty())
+        ),
+        _ => false,
+    }
+}
+
+/// Names of the primitive types, whose `Debug` output is a valid literal.
+pub const PRIMITIVE_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32",
+    "f64", "bool", "char",
+];
+
+/// Return types that are hard-coded without a mode, as listed in error messages.
+///
+/// `T`, `E`, `K` and `V` stand for any supported type, except in slices where `T` is a primitive type.
+pub const SUPPORTED_TYPES: &[&str] = &[
+    "a primitive type",
+    "an array or tuple (of up to 12 elements) of primitive types",
+    "`&'static str`",
+    "`String`",
+    "`Option<T>`",
+    "`Result<T, E>`",
+    "`Vec<T>`",
+    "a tuple of up to 12 supported types",
+    "`BTreeMap<K, V>`",
+    "`BTreeSet<T>`",
+    "`&'static [T]`",
+    "`Box<T>`, `Rc<T>` or `Arc<T>` of a supported type, `str` or `[T]`",
+    "`Cow<'static, str>` or `Cow<'static, [T]>`",
+    "`Ipv4Addr`",
+    "`Ipv6Addr`",
+    "`SocketAddr`",
+    "`SystemTime`",
+    "`PathBuf`",
+    "`&'static Path`",
+    "`&'static CStr`",
+    "`Range<T>` or `RangeInclusive<T>` of a primitive type",
+    "`Wrapping<T>` or `Saturating<T>` of a primitive type",
+    "`NonZeroU32` and the other non-zero integers, or `NonZero<T>`",
+];
+
+pub fn is_primitive_type(input: &syn::Type) -> bool {
+    match input {
+        syn::Type::Path(i) if i.path.leading_colon.is_none() && i.path.segments.len() == 1 => {
+            PRIMITIVE_TYPES.contains(&i.path.segments[0].ident.to_string().as_str())
+        }
+        syn::Type::Reference(i) => is_static_str(i),
+        syn::Type::Array(i) => is_primitive_type(&i.elem),
+        syn::Type::Group(syn::TypeGroup { elem, .. })
+        | syn::Type::Paren(syn::TypeParen { elem, .. }) => is_primitive_type(elem),
+        syn::Type::Slice(i) => is_primitive_type(&i.elem),
+        syn::Type::Tuple(i) => {
+            i.elems.len() <= MAX_TUPLE_LEN && i.elems.iter().all(is_primitive_type)
+        }
+        _ => false,
+    }
+}
+
+/// The standard library only implements `Debug` for tuples of up to 12 elements.
+pub const MAX_TUPLE_LEN: usize = 12;
+
+/// Smart pointers that can be rebuilt from a borrowed slice.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SlicePointer {
+    Box,
+    Rc,
+}
+
+/// Split a path type such as `std::rc::Rc<[u8]>` into its last identifier and its type arguments.
+pub fn generic_type(input: &syn::Type) -> Option<(&syn::Ident, Vec<&syn::Type>)> {
+    let segment = match input {
+        syn::Type::Path(i) if i.qself.is_none() => i.path.segments.last()?,
+        syn::Type::Group(syn::TypeGroup { elem, .. })
+        | syn::Type::Paren(syn::TypeParen { elem, .. }) => return generic_type(elem),
+        _ => return None,
+    };
+    let arguments = match &segment.arguments {
+        syn::PathArguments::None => Vec::new(),
+        syn::PathArguments::AngleBracketed(i) => i
+            .args
+            .iter()
+            .filter_map(|i| match i {
+                syn::GenericArgument::Type(i) => Some(i),
+                _ => None,
+            })
+            .collect(),
+        syn::PathArguments::Parenthesized(_) => return None,
+    };
+    Some((&segment.ident, arguments))
+}
+
+/// Match `Box<[T]>` and `Rc<[T]>` of a primitive `T`, returning the pointer and `T`.
+pub fn owned_slice(input: &syn::Type) -> Option<(SlicePointer, &syn::Type)> {
+    let (ident, arguments) = generic_type(input)?;
+    let pointer = match ident.to_string().as_str() {
+        "Box" => SlicePointer::Box,
+        "Rc" => SlicePointer::Rc,
+        _ => return None,
+    };
+    match arguments.as_slice() {
+        [syn::Type::Slice(i)] if is_primitive_type(&i.elem) => Some((pointer, &i.elem)),
+        _ => None,
+    }
+}
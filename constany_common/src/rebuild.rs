@@ -0,0 +1,1140 @@
+//! Rebuild values from the tokens of their `Debug` output.
+//!
+//! Stage one records every supported value with `{:?}`, after `capture` has replaced the parts whose `Debug` output is hard to read back (eg. `::1` of an `Ipv6Addr`) with their fields.
+//! Stage two lexes the record and walks it alongside the return type, turning each part that is not a valid expression by itself (eg. `{1: 2}` of a map) into code that constructs it.
+
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+use quote::quote;
+
+/// Types that can be rebuilt from their `Debug` output.
+enum Shape<'a> {
+    /// `Debug` output is already a valid constant expression.
+    Literal,
+    /// `&'static [T]` of a `T` whose `Debug` output is a literal, eg. `&'static [&'static str]`.
+    StaticSlice(&'a syn::Type),
+    String,
+    /// A tuple of supported types, some of which are not primitive, eg. `(String, u32)`.
+    Tuple(Vec<&'a syn::Type>),
+    /// `Box<T>`, `Rc<T>` or `Arc<T>` of `str`, a slice of a primitive type, or another supported type, whose `Debug` output is the one of `T`.
+    Pointer(&'a syn::Ident, &'a syn::Type),
+    /// `Cow<'static, str>` or `Cow<'static, [T]>` of a primitive `T`.
+    Cow(&'a syn::Type),
+    Option(&'a syn::Type),
+    Result(&'a syn::Type, &'a syn::Type),
+    Vec(&'a syn::Type),
+    BTreeMap(&'a syn::Type, &'a syn::Type),
+    BTreeSet(&'a syn::Type),
+    /// Captured as its octets.
+    Ipv4Addr,
+    /// Captured as its octets.
+    Ipv6Addr,
+    /// Captured as the octets of its address and its port.
+    SocketAddr,
+    /// Captured as whether it is after `UNIX_EPOCH`, and the seconds and nanoseconds from it.
+    SystemTime,
+    /// Captured as a string.
+    PathBuf,
+    /// `&'static Path`, captured as a string.
+    StaticPath,
+    /// `Range<T>` of a primitive `T`, captured as its bounds.
+    Range(&'a syn::Type),
+    /// `RangeInclusive<T>` of a primitive `T`, captured as its bounds.
+    RangeInclusive(&'a syn::Type),
+    /// `Wrapping<T>` or `Saturating<T>` of a primitive `T`, whose `Debug` output is the one of `T`.
+    Numeric(&'a syn::Ident),
+    /// `NonZeroU32` and the like, or `NonZero<T>` of an integer `T`, whose `Debug` output is the one of the integer.
+    NonZero(&'a syn::Ident, Option<&'a syn::Type>),
+}
+
+/// Integer types, which have a non-zero counterpart.
+const INTEGER_TYPES: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+];
+
+fn shape(input: &syn::Type) -> Option<Shape<'_>> {
+    // Redundant parentheses, eg. `(u32)`, do not change the type.
+    if let syn::Type::Group(syn::TypeGroup { elem, .. })
+    | syn::Type::Paren(syn::TypeParen { elem, .. }) = input
+    {
+        return shape(elem);
+    }
+    if crate::is_primitive_type(input) {
+        return Some(Shape::Literal);
+    }
+    if let Some(slice) = crate::static_slice(input) {
+        return Some(Shape::StaticSlice(slice));
+    }
+    if let syn::Type::Tuple(i) = input {
+        return if i.elems.len() <= crate::MAX_TUPLE_LEN && i.elems.iter().all(is_rebuildable) {
+            Some(Shape::Tuple(i.elems.iter().collect()))
+        } else {
+            None
+        };
+    }
+    if let syn::Type::Reference(i) = input {
+        return match crate::static_reference(i).and_then(crate::generic_type) {
+            Some((ident, arguments)) if ident == "Path" && arguments.is_empty() => {
+                Some(Shape::StaticPath)
+            }
+            _ => None,
+        };
+    }
+    let (ident, arguments) = crate::generic_type(input)?;
+    match (ident.to_string().as_str(), arguments.as_slice()) {
+        ("String", []) => Some(Shape::String),
+        ("Box", [inner]) | ("Rc", [inner]) | ("Arc", [inner])
+            if crate::is_bare_str(inner) || is_rebuildable(inner) =>
+        {
+            Some(Shape::Pointer(ident, inner))
+        }
+        ("Cow", [inner]) if crate::is_bare_str(inner) || slice_element(inner).is_some() => {
+            Some(Shape::Cow(inner))
+        }
+        ("Option", [inner]) if is_rebuildable(inner) => Some(Shape::Option(inner)),
+        ("Result", [ok, err]) if is_rebuildable(ok) && is_rebuildable(err) => {
+            Some(Shape::Result(ok, err))
+        }
+        ("Vec", [element]) if is_rebuildable(element) => Some(Shape::Vec(element)),
+        ("BTreeMap", [key, value]) if is_rebuildable(key) && is_rebuildable(value) => {
+            Some(Shape::BTreeMap(key, value))
+        }
+        ("BTreeSet", [element]) if is_rebuildable(element) => Some(Shape::BTreeSet(element)),
+        ("Ipv4Addr", []) => Some(Shape::Ipv4Addr),
+        ("Ipv6Addr", []) => Some(Shape::Ipv6Addr),
+        ("SocketAddr", []) => Some(Shape::SocketAddr),
+        ("SystemTime", []) => Some(Shape::SystemTime),
+        ("PathBuf", []) => Some(Shape::PathBuf),
+        ("Range", [inner]) if crate::is_primitive_type(inner) => Some(Shape::Range(inner)),
+        ("RangeInclusive", [inner]) if crate::is_primitive_type(inner) => {
+            Some(Shape::RangeInclusive(inner))
+        }
+        ("Wrapping", [inner]) | ("Saturating", [inner]) if crate::is_primitive_type(inner) => {
+            Some(Shape::Numeric(ident))
+        }
+        ("NonZero", [inner])
+            if INTEGER_TYPES
+                .iter()
+                .any(|i| matches!(inner, syn::Type::Path(j) if j.path.is_ident(i))) =>
+        {
+            Some(Shape::NonZero(ident, Some(inner)))
+        }
+        (name, [])
+            if INTEGER_TYPES
+                .iter()
+                .any(|i| name == format!("NonZero{}{}", i[..1].to_uppercase(), &i[1..])) =>
+        {
+            Some(Shape::NonZero(ident, None))
+        }
+        _ => None,
+    }
+}
+
+/// The element type of `[T]` of a primitive `T`.
+fn slice_element(input: &syn::Type) -> Option<&syn::Type> {
+    match input {
+        syn::Type::Slice(i) if crate::is_primitive_type(&i.elem) => Some(&i.elem),
+        syn::Type::Group(syn::TypeGroup { elem, .. })
+        | syn::Type::Paren(syn::TypeParen { elem, .. }) => slice_element(elem),
+        _ => None,
+    }
+}
+
+/// Whether `rebuild` knows how to construct the type.
+pub fn is_rebuildable(input: &syn::Type) -> bool {
+    shape(input).is_some()
+}
+
+/// Whether the rebuilt value can be constructed in a `const fn`.
+pub fn is_const(input: &syn::Type) -> bool {
+    match shape(input) {
+        Some(Shape::Literal)
+        | Some(Shape::StaticSlice(_))
+        | Some(Shape::Range(_))
+        | Some(Shape::RangeInclusive(_))
+        | Some(Shape::Numeric(_))
+        | Some(Shape::NonZero(..))
+        | Some(Shape::Cow(_)) => true,
+        Some(Shape::Option(inner)) => is_const(inner),
+        Some(Shape::Result(ok, err)) => is_const(ok) && is_const(err),
+        Some(Shape::Tuple(elements)) => elements.iter().all(|i| is_const(i)),
+        _ => false,
+    }
+}
+
+/// Convert `value` of type `input` into something whose `Debug` output `rebuild` can read.
+pub fn capture(input: &syn::Type, value: TokenStream) -> TokenStream {
+    match shape(input) {
+        Some(Shape::Option(inner)) if needs_capture(inner) => {
+            let v = capture(inner, quote! { v });
+            quote! { #value.map(|v| #v) }
+        }
+        Some(Shape::Result(ok, err)) if needs_capture(ok) || needs_capture(err) => {
+            let v = capture(ok, quote! { v });
+            let e = capture(err, quote! { e });
+            quote! { #value.map(|v| #v).map_err(|e| #e) }
+        }
+        Some(Shape::Vec(element)) if needs_capture(element) => {
+            let v = capture(element, quote! { v });
+            quote! {
+                #value.into_iter().map(|v| #v).collect::<Vec<_>>()
+            }
+        }
+        Some(Shape::BTreeMap(key, value_type))
+            if needs_capture(key) || needs_capture(value_type) =>
+        {
+            let k = capture(key, quote! { k });
+            let v = capture(value_type, quote! { v });
+            quote! {
+                #value
+                    .into_iter()
+                    .map(|(k, v)| (#k, #v))
+                    .collect::<std::collections::BTreeMap<_, _>>()
+            }
+        }
+        Some(Shape::BTreeSet(element)) if needs_capture(element) => {
+            let v = capture(element, quote! { v });
+            quote! {
+                #value
+                    .into_iter()
+                    .map(|v| #v)
+                    .collect::<std::collections::BTreeSet<_>>()
+            }
+        }
+        Some(Shape::Literal) => float_bits(input, value),
+        Some(Shape::StaticSlice(slice)) => float_bits(slice, value),
+        Some(Shape::Cow(inner)) => float_bits(inner, value),
+        Some(Shape::Pointer(_, inner)) if slice_element(inner).is_some() => {
+            float_bits(inner, value)
+        }
+        Some(Shape::Pointer(_, inner)) if needs_capture(inner) => {
+            capture(inner, quote! { (*#value).clone() })
+        }
+        Some(Shape::Tuple(elements)) if elements.iter().any(|i| needs_capture(i)) => {
+            let fields = elements.iter().enumerate().map(|(i, j)| {
+                let index = syn::Index::from(i);
+                capture(j, quote! { value.#index })
+            });
+            quote! {
+                {
+                    let value = #value;
+                    (#(#fields,)*)
+                }
+            }
+        }
+        Some(Shape::Ipv4Addr) | Some(Shape::Ipv6Addr) => quote! { #value.octets() },
+        Some(Shape::SocketAddr) => quote! {
+            {
+                let value = #value;
+                let octets = match value.ip() {
+                    std::net::IpAddr::V4(i) => i.octets().to_vec(),
+                    std::net::IpAddr::V6(i) => i.octets().to_vec(),
+                };
+                (octets, value.port())
+            }
+        },
+        Some(Shape::SystemTime) => match crate::frozen_time() {
+            Some(secs) => quote! {
+                {
+                    let _ = #value;
+                    (true, #secs, 0u32)
+                }
+            },
+            None => quote! {
+                match #value.duration_since(std::time::UNIX_EPOCH) {
+                    Ok(i) => (true, i.as_secs(), i.subsec_nanos()),
+                    Err(i) => (false, i.duration().as_secs(), i.duration().subsec_nanos()),
+                }
+            },
+        },
+        // `Debug` of a path escapes invalid UTF-8 in a way that is not a valid string literal.
+        Some(Shape::PathBuf) | Some(Shape::StaticPath) => quote! {
+            {
+                let value = #value;
+                let path: &std::path::Path = value.as_ref();
+                if path.is_absolute() {
+                    eprintln!(
+                        "constany: warning: hard-coding the absolute path {:?}, which may only exist on the build machine",
+                        path
+                    );
+                }
+                path.to_str()
+                    .expect("constany can only hard-code paths that are valid UTF-8")
+                    .to_string()
+            }
+        },
+        // `Debug` of an exhausted `RangeInclusive` has a suffix, so the bounds are recorded instead.
+        Some(Shape::Range(inner)) => {
+            let start = float_bits(inner, quote! { value.start });
+            let end = float_bits(inner, quote! { value.end });
+            quote! {
+                {
+                    let value = #value;
+                    (#start, #end)
+                }
+            }
+        }
+        Some(Shape::RangeInclusive(inner)) => {
+            let start = float_bits(inner, quote! { start });
+            let end = float_bits(inner, quote! { end });
+            quote! {
+                {
+                    let (start, end) = #value.into_inner();
+                    (#start, #end)
+                }
+            }
+        }
+        _ => value,
+    }
+}
+
+/// Whether a primitive type contains floats.
+pub fn has_float(input: &syn::Type) -> bool {
+    match input {
+        syn::Type::Path(i) => i.path.is_ident("f32") || i.path.is_ident("f64"),
+        syn::Type::Array(syn::TypeArray { elem, .. })
+        | syn::Type::Slice(syn::TypeSlice { elem, .. })
+        | syn::Type::Group(syn::TypeGroup { elem, .. })
+        | syn::Type::Paren(syn::TypeParen { elem, .. }) => has_float(elem),
+        syn::Type::Tuple(i) => i.elems.iter().any(has_float),
+        _ => false,
+    }
+}
+
+/// Replace the floats in a value of a primitive type with their bits, so that the sign and payload of a `NaN` are recorded too.
+fn float_bits(input: &syn::Type, value: TokenStream) -> TokenStream {
+    match input {
+        _ if !has_float(input) => value,
+        syn::Type::Array(i) => {
+            let v = float_bits(&i.elem, quote! { v });
+            quote! { #value.map(|v| #v) }
+        }
+        // A slice is behind a reference or a pointer, and its elements are copied out of it.
+        syn::Type::Slice(i) => {
+            let v = float_bits(&i.elem, quote! { (*v) });
+            quote! { #value.iter().map(|v| #v).collect::<Vec<_>>() }
+        }
+        syn::Type::Tuple(i) => {
+            let fields = i.elems.iter().enumerate().map(|(i, j)| {
+                let index = syn::Index::from(i);
+                float_bits(j, quote! { value.#index })
+            });
+            quote! {
+                {
+                    let value = #value;
+                    (#(#fields,)*)
+                }
+            }
+        }
+        syn::Type::Group(syn::TypeGroup { elem, .. })
+        | syn::Type::Paren(syn::TypeParen { elem, .. }) => float_bits(elem, value),
+        _ => quote! { #value.to_bits() },
+    }
+}
+
+/// Whether `capture` changes values of the type.
+fn needs_capture(input: &syn::Type) -> bool {
+    match shape(input) {
+        Some(Shape::Option(inner)) => needs_capture(inner),
+        Some(Shape::Result(ok, err)) => needs_capture(ok) || needs_capture(err),
+        Some(Shape::Vec(element)) => needs_capture(element),
+        Some(Shape::BTreeMap(key, value)) => needs_capture(key) || needs_capture(value),
+        Some(Shape::BTreeSet(element)) => needs_capture(element),
+        Some(Shape::Pointer(_, inner)) => !crate::is_bare_str(inner) && needs_capture(inner),
+        Some(Shape::Tuple(elements)) => elements.iter().any(|i| needs_capture(i)),
+        Some(Shape::Literal) => has_float(input),
+        Some(Shape::StaticSlice(inner)) | Some(Shape::Cow(inner)) => has_float(inner),
+        Some(Shape::Ipv4Addr)
+        | Some(Shape::Ipv6Addr)
+        | Some(Shape::SocketAddr)
+        | Some(Shape::SystemTime)
+        | Some(Shape::PathBuf)
+        | Some(Shape::StaticPath)
+        | Some(Shape::Range(_))
+        | Some(Shape::RangeInclusive(_)) => true,
+        _ => false,
+    }
+}
+
+/// Turn the `Debug` output of a value of type `input` into an expression constructing it.
+pub fn rebuild(input: &syn::Type, tokens: TokenStream) -> Result<TokenStream, String> {
+    match shape(input) {
+        None => Err(format!("`{}` cannot be rebuilt", quote!(#input))),
+        Some(Shape::Literal) => Ok(literal(input, tokens)),
+        // The array of literals is promoted to a `'static` constant.
+        Some(Shape::StaticSlice(slice)) => {
+            let elements = literal(slice, tokens);
+            Ok(quote! { &#elements })
+        }
+        Some(Shape::String) => Ok(quote! { String::from(#tokens) }),
+        Some(Shape::Tuple(elements)) => {
+            let fields = split(group(tokens, Delimiter::Parenthesis)?, ',');
+            if fields.len() != elements.len() {
+                return Err("Broken tuple".to_string());
+            }
+            let fields = elements
+                .iter()
+                .zip(fields)
+                .map(|(i, j)| rebuild(i, j))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(quote! { (#(#fields,)*) })
+        }
+        Some(Shape::Pointer(pointer, inner)) => {
+            let pointer = match pointer.to_string().as_str() {
+                "Box" => quote! { Box },
+                "Rc" => quote! { std::rc::Rc },
+                _ => quote! { std::sync::Arc },
+            };
+            if crate::is_bare_str(inner) {
+                Ok(quote! { #pointer::<str>::from(#tokens) })
+            } else if slice_element(inner).is_some() {
+                let elements = typed_slice(inner, tokens);
+                Ok(quote! { #pointer::<#inner>::from(#elements) })
+            } else {
+                let value = rebuild(inner, tokens)?;
+                Ok(quote! { #pointer::new(#value) })
+            }
+        }
+        Some(Shape::Cow(inner)) if slice_element(inner).is_some() => {
+            let elements = typed_slice(inner, tokens);
+            Ok(quote! { std::borrow::Cow::Borrowed(#elements) })
+        }
+        Some(Shape::Cow(_)) => Ok(quote! { std::borrow::Cow::Borrowed(#tokens) }),
+        Some(Shape::Option(inner)) => match variant(tokens)? {
+            (name, None) if name == "None" => Ok(quote! { None }),
+            (name, Some(i)) if name == "Some" => {
+                let i = rebuild(inner, i)?;
+                Ok(quote! { Some(#i) })
+            }
+            _ => Err("Broken option".to_string()),
+        },
+        Some(Shape::Result(ok, err)) => match variant(tokens)? {
+            (name, Some(i)) if name == "Ok" => {
+                let i = rebuild(ok, i)?;
+                Ok(quote! { Ok(#i) })
+            }
+            (name, Some(i)) if name == "Err" => {
+                let i = rebuild(err, i)?;
+                Ok(quote! { Err(#i) })
+            }
+            _ => Err("Broken result".to_string()),
+        },
+        Some(Shape::Vec(element)) => {
+            let elements = split(group(tokens, Delimiter::Bracket)?, ',')
+                .into_iter()
+                .map(|i| rebuild(element, i))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(quote! { vec![#(#elements),*] })
+        }
+        Some(Shape::BTreeMap(key, value)) => {
+            let mut inserts = quote! {};
+            for entry in split(group(tokens, Delimiter::Brace)?, ',') {
+                let (k, v) = match split(entry, ':').as_slice() {
+                    [k, v] => (rebuild(key, k.clone())?, rebuild(value, v.clone())?),
+                    _ => return Err("Broken map entry".to_string()),
+                };
+                inserts = quote! {
+                    #inserts
+                    map.insert(#k, #v);
+                };
+            }
+            Ok(quote! {
+                {
+                    #[allow(unused_mut)]
+                    let mut map = std::collections::BTreeMap::new();
+                    #inserts
+                    map
+                }
+            })
+        }
+        Some(Shape::BTreeSet(element)) => {
+            let elements = split(group(tokens, Delimiter::Brace)?, ',')
+                .into_iter()
+                .map(|i| rebuild(element, i))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(quote! {
+                {
+                    #[allow(unused_mut)]
+                    let mut set = std::collections::BTreeSet::new();
+                    #(set.insert(#elements);)*
+                    set
+                }
+            })
+        }
+        Some(Shape::Ipv4Addr) => Ok(quote! { std::net::Ipv4Addr::from(#tokens) }),
+        Some(Shape::Ipv6Addr) => Ok(quote! { std::net::Ipv6Addr::from(#tokens) }),
+        Some(Shape::SocketAddr) => {
+            match split(group(tokens, Delimiter::Parenthesis)?, ',').as_slice() {
+                [octets, port] => Ok(quote! {
+                    std::net::SocketAddr::new(std::net::IpAddr::from(#octets), #port)
+                }),
+                _ => Err("Broken socket address".to_string()),
+            }
+        }
+        Some(Shape::SystemTime) => {
+            match split(group(tokens, Delimiter::Parenthesis)?, ',').as_slice() {
+                [after, secs, nanos] => {
+                    let operator = if after.to_string() == "true" {
+                        quote! { + }
+                    } else {
+                        quote! { - }
+                    };
+                    Ok(quote! {
+                        std::time::UNIX_EPOCH #operator std::time::Duration::new(#secs, #nanos)
+                    })
+                }
+                _ => Err("Broken system time".to_string()),
+            }
+        }
+        Some(Shape::Numeric(ident)) => Ok(quote! { std::num::#ident(#tokens) }),
+        // The type makes zero impossible, unless the resource was edited.
+        Some(Shape::NonZero(ident, inner)) => {
+            if tokens.to_string() == "0" {
+                return Err(format!("`{}` cannot be zero", quote!(#input)));
+            }
+            let inner = inner.map(|i| quote! { ::<#i> });
+            Ok(quote! {
+                match std::num::#ident #inner::new(#tokens) {
+                    Some(value) => value,
+                    None => panic!("zero"),
+                }
+            })
+        }
+        Some(Shape::PathBuf) => Ok(quote! { std::path::PathBuf::from(#tokens) }),
+        Some(Shape::StaticPath) => Ok(quote! { std::path::Path::new(#tokens) }),
+        Some(Shape::Range(inner)) | Some(Shape::RangeInclusive(inner)) => {
+            match split(group(tokens, Delimiter::Parenthesis)?, ',').as_slice() {
+                [start, end] => {
+                    let start = literal(inner, start.clone());
+                    let end = literal(inner, end.clone());
+                    match shape(input) {
+                        Some(Shape::Range(_)) => Ok(quote! { #start..#end }),
+                        _ => Ok(quote! { #start..=#end }),
+                    }
+                }
+                _ => Err("Broken range".to_string()),
+            }
+        }
+    }
+}
+
+/// Turn the recorded keys of a set of `&'static str` (a sorted `Debug` list) into a `phf::Set`, whose perfect hash `phf_set!` computes at compile time.
+pub fn phf_set(tokens: TokenStream) -> Result<TokenStream, String> {
+    let keys = split(group(tokens, Delimiter::Bracket)?, ',');
+    Ok(quote! { ::phf::phf_set! { #(#keys),* } })
+}
+
+/// Number of elements in the recorded `Debug` output of a slice or an array.
+pub fn len(tokens: TokenStream) -> Result<usize, String> {
+    Ok(split(group(tokens, Delimiter::Bracket)?, ',').len())
+}
+
+/// A `&'static [T]` of the literals, typed so that integers are not inferred as `i32`. Unlike an index, a constant is allowed in a `const fn`.
+fn typed_slice(input: &syn::Type, tokens: TokenStream) -> TokenStream {
+    let elements = literal(input, tokens);
+    quote! {
+        {
+            const VALUE: &#input = &#elements;
+            VALUE
+        }
+    }
+}
+
+/// Turn floats recorded by their bits into `from_bits`, and the `Debug` output of special floats (`NaN` and `inf`), which is not a valid expression, into the matching constant.
+fn literal(input: &syn::Type, tokens: TokenStream) -> TokenStream {
+    match input {
+        syn::Type::Path(i) if i.path.is_ident("f32") || i.path.is_ident("f64") => {
+            let float = &i.path;
+            tokens
+                .into_iter()
+                .map(|j| match &j {
+                    TokenTree::Ident(k) if k == "NaN" => quote! { #float::NAN },
+                    TokenTree::Ident(k) if k == "inf" => quote! { #float::INFINITY },
+                    // `Debug` never prints a float without a `.` or an exponent.
+                    TokenTree::Literal(k) => match k.to_string().parse::<u64>() {
+                        Ok(bits) => {
+                            let bits: TokenStream = format!("{:#x}", bits).parse().unwrap();
+                            quote! { #float::from_bits(#bits) }
+                        }
+                        Err(_) => j.into(),
+                    },
+                    _ => j.into(),
+                })
+                .collect()
+        }
+        syn::Type::Array(syn::TypeArray { elem, .. })
+        | syn::Type::Slice(syn::TypeSlice { elem, .. }) => {
+            match group(tokens.clone(), Delimiter::Bracket) {
+                Ok(elements) => {
+                    let elements = split(elements, ',').into_iter().map(|i| literal(elem, i));
+                    quote! { [#(#elements),*] }
+                }
+                Err(_) => tokens,
+            }
+        }
+        syn::Type::Tuple(i) => match group(tokens.clone(), Delimiter::Parenthesis) {
+            Ok(elements) => {
+                let elements = i
+                    .elems
+                    .iter()
+                    .zip(split(elements, ','))
+                    .map(|(j, k)| literal(j, k));
+                quote! { (#(#elements,)*) }
+            }
+            Err(_) => tokens,
+        },
+        syn::Type::Group(syn::TypeGroup { elem, .. })
+        | syn::Type::Paren(syn::TypeParen { elem, .. }) => literal(elem, tokens),
+        _ => tokens,
+    }
+}
+
+/// Rebuild a value of a user type from its derived `Debug` output (the "debug + pub" solution).
+///
+/// Structs and enum variants are rebuilt through the path of the return type (eg. `Red` becomes `colors::Color::Red`), so that the type does not need to be imported. Other output is kept as-is.
+pub fn debug_pub(input: &syn::Type, tokens: TokenStream) -> Result<TokenStream, String> {
+    let mut path = match input {
+        syn::Type::Path(i) if i.qself.is_none() => i.path.clone(),
+        _ => return Ok(tokens),
+    };
+    let type_name = match path.segments.last_mut() {
+        Some(i) => {
+            i.arguments = syn::PathArguments::None;
+            i.ident.clone()
+        }
+        None => return Ok(tokens),
+    };
+    let mut parts = tokens.clone().into_iter();
+    let constructor = |name: &proc_macro2::Ident| {
+        if *name == type_name {
+            quote! { #path }
+        } else {
+            quote! { #path::#name }
+        }
+    };
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(TokenTree::Ident(name)), None, None) => Ok(constructor(&name)),
+        (Some(TokenTree::Ident(name)), Some(TokenTree::Group(fields)), None)
+            if fields.delimiter() == Delimiter::Parenthesis =>
+        {
+            let fields = fields.stream();
+            if !is_literal(&fields) {
+                return Err(format!(
+                    "`{}` can only be rebuilt from `Debug` if its fields are primitive",
+                    name
+                ));
+            }
+            let constructor = constructor(&name);
+            Ok(quote! { #constructor(#fields) })
+        }
+        (Some(TokenTree::Ident(name)), Some(TokenTree::Group(fields)), None)
+            if fields.delimiter() == Delimiter::Brace =>
+        {
+            let fields = fields.stream();
+            let named_literals =
+                split(fields.clone(), ',')
+                    .iter()
+                    .all(|i| match split(i.clone(), ':').as_slice() {
+                        [field, value] => {
+                            matches!(
+                                field.clone().into_iter().collect::<Vec<_>>().as_slice(),
+                                [TokenTree::Ident(_)]
+                            ) && is_literal(value)
+                        }
+                        _ => false,
+                    });
+            if !named_literals {
+                return Err(format!(
+                    "`{}` can only be rebuilt from `Debug` if its fields are primitive",
+                    name
+                ));
+            }
+            let constructor = constructor(&name);
+            Ok(quote! { #constructor { #fields } })
+        }
+        _ => Ok(tokens),
+    }
+}
+
+/// Whether the tokens only contain literals of primitive types, and arrays or tuples of them.
+fn is_literal(tokens: &TokenStream) -> bool {
+    tokens.clone().into_iter().all(|i| match i {
+        TokenTree::Literal(_) => true,
+        TokenTree::Punct(j) => matches!(j.as_char(), '-' | ','),
+        TokenTree::Ident(j) => j == "true" || j == "false",
+        TokenTree::Group(j) => j.delimiter() != Delimiter::Brace && is_literal(&j.stream()),
+    })
+}
+
+/// Split an enum variant such as `Some(1)` or `None` into its name and its only field.
+fn variant(tokens: TokenStream) -> Result<(proc_macro2::Ident, Option<TokenStream>), String> {
+    let mut tokens = tokens.into_iter();
+    match (tokens.next(), tokens.next(), tokens.next()) {
+        (Some(TokenTree::Ident(name)), None, None) => Ok((name, None)),
+        (Some(TokenTree::Ident(name)), Some(TokenTree::Group(field)), None)
+            if field.delimiter() == Delimiter::Parenthesis =>
+        {
+            Ok((name, Some(field.stream())))
+        }
+        _ => Err("Expected an enum variant".to_string()),
+    }
+}
+
+/// Unwrap the contents of a single delimited group.
+fn group(tokens: TokenStream, delimiter: Delimiter) -> Result<TokenStream, String> {
+    let mut tokens = tokens.into_iter();
+    match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Group(i)), None) if i.delimiter() == delimiter => Ok(i.stream()),
+        _ => Err(format!("Expected a {:?} group", delimiter)),
+    }
+}
+
+/// Split tokens on a top-level punctuation, which is not part of a longer operator such as `::`.
+fn split(tokens: TokenStream, separator: char) -> Vec<TokenStream> {
+    let mut parts = Vec::new();
+    let mut current = Vec::new();
+    let mut joint = false;
+    for i in tokens {
+        let is_separator = match &i {
+            TokenTree::Punct(j) => {
+                let is_separator =
+                    !joint && j.as_char() == separator && j.spacing() == Spacing::Alone;
+                joint = j.spacing() == Spacing::Joint;
+                is_separator
+            }
+            _ => {
+                joint = false;
+                false
+            }
+        };
+        if is_separator {
+            parts.push(current.drain(..).collect());
+        } else {
+            current.push(i);
+        }
+    }
+    if !current.is_empty() {
+        parts.push(current.into_iter().collect());
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rebuild the `Debug` output of `value` as stage two would, and parse it back as an array expression.
+    fn rebuild_array<T: std::fmt::Debug>(input: &str, value: T) -> Vec<syn::Lit> {
+        let input: syn::Type = syn::parse_str(input).unwrap();
+        let tokens = rebuild(&input, format!("{:?}", value).parse().unwrap()).unwrap();
+        match syn::parse2(tokens).unwrap() {
+            syn::Expr::Array(i) => i
+                .elems
+                .into_iter()
+                .map(|j| match j {
+                    syn::Expr::Lit(k) => k.lit,
+                    _ => panic!("Expected a literal"),
+                })
+                .collect(),
+            _ => panic!("Expected an array"),
+        }
+    }
+
+    #[test]
+    fn char_array() {
+        let value = [
+            'a', '\'', '"', '\\', '\0', '\n', '\u{301}', '\u{200b}', '\u{feff}', '🦀',
+        ];
+        let rebuilt = rebuild_array("[char; 10]", value)
+            .into_iter()
+            .map(|i| match i {
+                syn::Lit::Char(j) => j.value(),
+                _ => panic!("Expected a char"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rebuilt, value);
+    }
+
+    #[test]
+    fn bool_array() {
+        let value = [true, false, false, true];
+        let rebuilt = rebuild_array("[bool; 4]", value)
+            .into_iter()
+            .map(|i| match i {
+                syn::Lit::Bool(j) => j.value,
+                _ => panic!("Expected a bool"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(rebuilt, value);
+    }
+
+    #[test]
+    fn nested_array() {
+        let input: syn::Type = syn::parse_str("[[[f64; 2]; 1]; 2]").unwrap();
+        let value = [[[1.5, f64::NAN]], [[-f64::INFINITY, 0.0]]];
+        let rebuilt = rebuild(&input, format!("{:?}", value).parse().unwrap()).unwrap();
+        let expected: TokenStream = "[[[1.5, f64::NAN]], [[-f64::INFINITY, 0.0]]]"
+            .parse()
+            .unwrap();
+        assert_eq!(rebuilt.to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn lengths() {
+        for (value, expected) in [
+            ("[]", 0),
+            ("[1, 2, 3]", 3),
+            ("[\n    1,\n    2,\n]", 2),
+            ("[\"a, b\", \"c\"]", 2),
+            ("[(1, 2), (3, 4)]", 2),
+        ] {
+            assert_eq!(len(value.parse().unwrap()), Ok(expected));
+        }
+        assert!(len("(1, 2)".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn exact_floats() {
+        let value = [
+            f64::from_bits(1),
+            f64::MIN_POSITIVE / 3.0,
+            f64::MAX,
+            -0.0,
+            0.1 + 0.2,
+            f64::INFINITY,
+            -f64::INFINITY,
+            1.0 / 3.0,
+        ];
+        let input: syn::Type = syn::parse_str("[f64; 8]").unwrap();
+        let rebuilt = rebuild(&input, format!("{:?}", value).parse().unwrap()).unwrap();
+        let rebuilt = match syn::parse2(rebuilt).unwrap() {
+            syn::Expr::Array(i) => i.elems,
+            _ => panic!("Expected an array"),
+        };
+        let rebuilt = rebuilt
+            .iter()
+            .map(|i| match quote!(#i).to_string().replace(' ', "").as_str() {
+                "f64::INFINITY" => f64::INFINITY,
+                "-f64::INFINITY" => -f64::INFINITY,
+                j => j.parse::<f64>().unwrap(),
+            })
+            .map(f64::to_bits)
+            .collect::<Vec<_>>();
+        assert_eq!(rebuilt, value.map(f64::to_bits));
+        let value = [f32::from_bits(1), f32::EPSILON, f32::MAX, -f32::INFINITY];
+        let input: syn::Type = syn::parse_str("[f32; 4]").unwrap();
+        let rebuilt = rebuild(&input, format!("{:?}", value).parse().unwrap()).unwrap();
+        assert_eq!(
+            rebuilt.to_string().replace(' ', ""),
+            format!("[1e-45,{:?},{:?},-f32::INFINITY]", f32::EPSILON, f32::MAX)
+        );
+        assert_eq!("1e-45".parse::<f32>().unwrap().to_bits(), 1);
+    }
+
+    #[test]
+    fn float_bits() {
+        let value = [
+            f32::from_bits(0x7fc0_1234),
+            f32::from_bits(0xffc0_0001),
+            -0.0,
+        ];
+        let input: syn::Type = syn::parse_str("[f32; 3]").unwrap();
+        let rebuilt = rebuild(
+            &input,
+            format!("{:?}", value.map(f32::to_bits)).parse().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            rebuilt.to_string().replace(' ', ""),
+            "[f32::from_bits(0x7fc01234),f32::from_bits(0xffc00001),f32::from_bits(0x80000000)]"
+        );
+        let input: syn::Type = syn::parse_str("(u8, [f64; 1])").unwrap();
+        assert_eq!(
+            capture(&input, quote! { pair() })
+                .to_string()
+                .replace(' ', ""),
+            "{letvalue=pair();(value.0,value.1.map(|v|v.to_bits()),)}"
+        );
+    }
+
+    #[test]
+    fn fieldless_enum() {
+        #[derive(Debug)]
+        enum Color {
+            Red,
+            Green,
+            Blue,
+        }
+        let input: syn::Type = syn::parse_str("colors::Color<u8>").unwrap();
+        for (value, expected) in [
+            (Color::Red, "colors::Color::Red"),
+            (Color::Green, "colors::Color::Green"),
+            (Color::Blue, "colors::Color::Blue"),
+        ] {
+            let rebuilt = debug_pub(&input, format!("{:?}", value).parse().unwrap()).unwrap();
+            assert_eq!(
+                rebuilt.to_string(),
+                expected.parse::<TokenStream>().unwrap().to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn box_str() {
+        let input: syn::Type = syn::parse_str("Box<str>").unwrap();
+        for value in ["", "tab\t \"quoted\" é\n🦀"] {
+            let value: Box<str> = value.into();
+            let rebuilt = rebuild(&input, format!("{:?}", value).parse().unwrap()).unwrap();
+            let rebuilt: syn::ExprCall = syn::parse2(rebuilt).unwrap();
+            match &rebuilt.args[0] {
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(i),
+                    ..
+                }) => assert_eq!(i.value(), &*value),
+                _ => panic!("Expected a string"),
+            }
+        }
+    }
+
+    #[test]
+    fn string_pairs() {
+        let input: syn::Type = syn::parse_str("&'static [(&'static str, &'static str)]").unwrap();
+        assert!(is_const(&input));
+        let values: [&[(&str, &str)]; 2] = [&[], &[("", "x"), ("k\"\\", "v\n\t🦀")]];
+        for value in values {
+            let rebuilt = rebuild(&input, format!("{:?}", value).parse().unwrap()).unwrap();
+            let rebuilt: syn::ExprReference = syn::parse2(rebuilt).unwrap();
+            let pairs = match *rebuilt.expr {
+                syn::Expr::Array(i) => i.elems,
+                _ => panic!("Expected an array"),
+            };
+            let pairs = pairs
+                .into_iter()
+                .map(|i| match i {
+                    syn::Expr::Tuple(j) => j
+                        .elems
+                        .into_iter()
+                        .map(|k| match k {
+                            syn::Expr::Lit(syn::ExprLit {
+                                lit: syn::Lit::Str(l),
+                                ..
+                            }) => l.value(),
+                            _ => panic!("Expected a string"),
+                        })
+                        .collect::<Vec<_>>(),
+                    _ => panic!("Expected a tuple"),
+                })
+                .collect::<Vec<_>>();
+            let expected = value
+                .iter()
+                .map(|(k, v)| vec![k.to_string(), v.to_string()])
+                .collect::<Vec<_>>();
+            assert_eq!(pairs, expected);
+        }
+    }
+
+    #[test]
+    fn ranges() {
+        for (input, value, expected) in [
+            ("Range<usize>", "(2, 7)", "2..7"),
+            ("std::ops::Range<i8>", "(-3, -1)", "-3..-1"),
+            ("Range<f64>", "(-inf, NaN)", "-f64::INFINITY..f64::NAN"),
+            ("RangeInclusive<char>", "('a', 'z')", "'a'..='z'"),
+        ] {
+            let input: syn::Type = syn::parse_str(input).unwrap();
+            let rebuilt = rebuild(&input, value.parse().unwrap()).unwrap();
+            assert_eq!(rebuilt.to_string().replace(' ', ""), expected);
+        }
+    }
+
+    #[test]
+    fn pointers() {
+        for (input, value, expected) in [
+            ("Rc<str>", "\"a\"", "std::rc::Rc::<str>::from(\"a\")"),
+            (
+                "std::sync::Arc<[u8]>",
+                "[1, 2]",
+                "std::sync::Arc::<[u8]>::from({ const VALUE: &[u8] = &[1, 2]; VALUE })",
+            ),
+            (
+                "Rc<String>",
+                "\"a\"",
+                "std::rc::Rc::new(String::from(\"a\"))",
+            ),
+            (
+                "Vec<Arc<str>>",
+                "[\"a\"]",
+                "vec![std::sync::Arc::<str>::from(\"a\")]",
+            ),
+            (
+                "Cow<'static, [f32]>",
+                "[NaN]",
+                "std::borrow::Cow::Borrowed({ const VALUE: &[f32] = &[f32::NAN]; VALUE })",
+            ),
+        ] {
+            let input: syn::Type = syn::parse_str(input).unwrap();
+            let rebuilt = rebuild(&input, value.parse().unwrap()).unwrap();
+            assert_eq!(
+                rebuilt.to_string().replace(' ', ""),
+                expected.replace(' ', "")
+            );
+        }
+    }
+
+    #[test]
+    fn tuples() {
+        for (input, value, expected) in [
+            (
+                "Vec<(&'static str, u32)>",
+                "[(\"a\\\"\", 1), (\"\", 2)]",
+                "vec![(\"a\\\"\", 1,), (\"\", 2,)]",
+            ),
+            ("Vec<(&'static str, u32)>", "[]", "vec![]"),
+            (
+                "(String, Option<u8>)",
+                "(\"a\", None)",
+                "(String::from(\"a\"), None,)",
+            ),
+            ("(String,)", "(\"a\",)", "(String::from(\"a\"),)"),
+            (
+                "(String, i32, bool)",
+                "(\"a, b\", -1, true)",
+                "(String::from(\"a, b\"), -1, true,)",
+            ),
+            (
+                "((u8, u8), String)",
+                "((1, 2), \"(3, 4)\")",
+                "((1, 2,), String::from(\"(3, 4)\"),)",
+            ),
+        ] {
+            let input: syn::Type = syn::parse_str(input).unwrap();
+            let rebuilt = rebuild(&input, value.parse().unwrap()).unwrap();
+            assert_eq!(
+                rebuilt.to_string().replace(' ', ""),
+                expected.replace(' ', "")
+            );
+        }
+        let twelve: syn::Type =
+            syn::parse_str(&format!("({})", ["String"; 12].join(", "))).unwrap();
+        let thirteen: syn::Type = syn::parse_str(&format!("({})", ["u8"; 13].join(", "))).unwrap();
+        assert!(is_rebuildable(&twelve));
+        assert!(!is_rebuildable(&thirteen));
+    }
+
+    #[test]
+    fn btree_set() {
+        for (input, value, expected) in [
+            (
+                "BTreeSet<char>",
+                "{'a', '}'}",
+                "{ #[allow(unused_mut)] let mut set = std::collections::BTreeSet::new(); set.insert('a'); set.insert('}'); set }",
+            ),
+            (
+                "BTreeSet<String>",
+                "{}",
+                "{ #[allow(unused_mut)] let mut set = std::collections::BTreeSet::new(); set }",
+            ),
+        ] {
+            let input: syn::Type = syn::parse_str(input).unwrap();
+            let rebuilt = rebuild(&input, value.parse().unwrap()).unwrap();
+            assert_eq!(
+                rebuilt.to_string().replace(' ', ""),
+                expected.replace(' ', "")
+            );
+        }
+    }
+
+    #[test]
+    fn numeric() {
+        for (input, value, expected) in [
+            (
+                "Wrapping<u8>",
+                format!("{:?}", std::num::Wrapping(250u8) + std::num::Wrapping(10)),
+                "std::num::Wrapping(4)",
+            ),
+            (
+                "std::num::Saturating<i16>",
+                format!(
+                    "{:?}",
+                    std::num::Saturating(i16::MIN) - std::num::Saturating(1)
+                ),
+                "std::num::Saturating(-32768)",
+            ),
+        ] {
+            let input: syn::Type = syn::parse_str(input).unwrap();
+            assert!(is_const(&input));
+            let rebuilt = rebuild(&input, value.parse().unwrap()).unwrap();
+            assert_eq!(
+                rebuilt.to_string().replace(' ', ""),
+                expected.replace(' ', "")
+            );
+        }
+    }
+
+    #[test]
+    fn parentheses() {
+        for (input, value, expected) in [
+            ("(u32)", "7", "7"),
+            ("(f64)", "NaN", "f64::NAN"),
+            (
+                "Option<(String)>",
+                "Some(\"a\")",
+                "Some(String::from(\"a\"))",
+            ),
+            ("((String, u8))", "(\"a\", 1)", "(String::from(\"a\"), 1,)"),
+        ] {
+            let input: syn::Type = syn::parse_str(input).unwrap();
+            assert!(crate::is_primitive_type(&input) || is_rebuildable(&input));
+            let rebuilt = rebuild(&input, value.parse().unwrap()).unwrap();
+            assert_eq!(
+                rebuilt.to_string().replace(' ', ""),
+                expected.replace(' ', "")
+            );
+        }
+    }
+
+    #[test]
+    fn phf() {
+        let keys = ["fn", "\"quoted\"", "é"];
+        let rebuilt = phf_set(format!("{:?}", keys).parse().unwrap()).unwrap();
+        assert_eq!(
+            rebuilt.to_string(),
+            ":: phf :: phf_set ! { \"fn\" , \"\\\"quoted\\\"\" , \"é\" }"
+        );
+        let rebuilt = phf_set("[]".parse().unwrap()).unwrap();
+        assert_eq!(rebuilt.to_string(), ":: phf :: phf_set ! { }");
+    }
+
+    #[test]
+    fn non_zero() {
+        for (input, value, expected) in [
+            (
+                "NonZeroU32",
+                format!("{:?}", std::num::NonZeroU32::new(7).unwrap()),
+                "match std::num::NonZeroU32::new(7) { Some(value) => value, None => panic!(\"zero\"), }",
+            ),
+            (
+                "std::num::NonZeroIsize",
+                format!("{:?}", std::num::NonZeroIsize::new(-3).unwrap()),
+                "match std::num::NonZeroIsize::new(-3) { Some(value) => value, None => panic!(\"zero\"), }",
+            ),
+            (
+                "NonZero<i8>",
+                format!("{:?}", std::num::NonZeroI8::new(i8::MIN).unwrap()),
+                "match std::num::NonZero::<i8>::new(-128) { Some(value) => value, None => panic!(\"zero\"), }",
+            ),
+        ] {
+            let input: syn::Type = syn::parse_str(input).unwrap();
+            assert!(is_const(&input));
+            let rebuilt = rebuild(&input, value.parse().unwrap()).unwrap();
+            assert_eq!(
+                rebuilt.to_string().replace(' ', ""),
+                expected.replace(' ', "")
+            );
+        }
+        let input: syn::Type = syn::parse_str("NonZeroU8").unwrap();
+        assert!(rebuild(&input, "0".parse().unwrap()).is_err());
+        for input in ["NonZero<f32>", "NonZeroF32", "NonZero<u8, u8>"] {
+            assert!(!is_rebuildable(&syn::parse_str(input).unwrap()));
+        }
+    }
+}
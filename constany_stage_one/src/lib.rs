@@ -110,7 +110,8 @@
 //! ```
 //! However, this will not work for most of the non-primitive type because their constructor is usually not `static`.
 //!
-//! There are two workaround for this: the `debug + pub` solution and `memop` solution.
+//! There are six workarounds for this: the `debug + pub` solution, the `memop` solution, the `serde` solution and its `lazy_json` variant, the `base64` solution, and the `phf` solution for sets of strings.
+//! One of them has to be picked with `#[const_fn(debug_pub)]`, `#[const_fn(memop)]`, `#[const_fn(serde)]`, `#[const_fn(lazy_json)]`, `#[const_fn(base64, from_bytes = "...")]` or `#[const_fn(phf)]`, otherwise the function fails to compile.
 //!
 //! The `debug + pub` solution first use `debug` trait to print the structure, and use the `pub` trait to rebuild it.
 //! This solution can recreate the structure without `unsafe` code. However, this require the structure to derive `Debug`.
@@ -119,137 +120,763 @@
 //! The `memop` solution transmute the memory directly.
 //! This solution can rebuild any structure, but please note that this method is `unsafe` and very dangerous.
 //! The generated function will be `fn` instead of `const_fn` because memory allocation is not allowed in `const`, although the memory itself is hard-coded inside the function.
+//!
+//! The `serde` solution records the value as JSON with `serde_json`, which the crate needs to depend on.
+//! The generated function will be `fn` and deserialize the value every time it is called.
+//! With `#[const_fn(lazy_json)]` instead, the function returns `&'static T` and the value is only deserialized on the first call.
+//!
+//! The `base64` solution records the bytes of a value implementing `AsRef<[u8]>`, and hard-codes them as a base64 string.
+//! The generated function decodes the string on every call and passes the bytes to the function given with `from_bytes`, which rebuilds the value.
 
 extern crate proc_macro;
 
 use crate::proc_macro::TokenStream;
+use constany_common::{rebuild, Method, Options};
 use quote::quote;
-use syn;
+use syn::parse::{Parse, ParseStream, Parser};
+use syn::punctuated::Punctuated;
 
-fn is_primitive_type(input: &syn::Type) -> bool {
-    match input {
-        syn::Type::Path(i) => {
-            if i.path.leading_colon.is_none() && i.path.segments.len() == 1 {
-                match i.path.segments[0].ident.to_string().as_str() {
-                    "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32"
-                    | "u64" | "u128" | "usize" | "f32" | "f64" | "bool" | "char" | "str" => true,
-                    _ => false,
+/// Generate a constant function
+#[proc_macro_attribute]
+pub fn const_fn(attr: TokenStream, bare_item: TokenStream) -> TokenStream {
+    let options = match Options::from_args(&syn::parse_macro_input!(attr as syn::AttributeArgs)) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let item: syn::ItemFn = syn::parse(bare_item.clone()).unwrap();
+    if let Err(e) = constany_common::check_single_stage(&item, "const_fn") {
+        return e.to_compile_error().into();
+    }
+    // A function simple enough for rustc does not need constany at all.
+    let looks_const = options.manual.is_none() && constany_common::looks_const(&item);
+    let item = match constany_common::rewrite(item, &options) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let name = &item.sig.ident;
+    // `main_fn` may call the wrapper from any module, whatever the visibility of the function, and from another crate when the function is `pub`.
+    let visibility = match &item.vis {
+        syn::Visibility::Public(_) => quote! { #[doc(hidden)] pub },
+        _ => quote! { pub(crate) },
+    };
+    let output_type = match constany_common::output_type(&item.sig) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let output_type = constany_common::value_type(output_type, &options);
+    if let Err(e) = constany_common::check_options(output_type, &options) {
+        return e.to_compile_error().into();
+    }
+    let (wrapper_fn_name, unchanged_fn_name) =
+        constany_common::wrapper_names(&name.to_string(), options.wrapper.as_ref());
+    // The wrapper takes the same parameters, which `main_fn` fills with the arguments in its list.
+    let names = constany_common::parameter_names(&item.sig);
+    let types = constany_common::parameter_types(&item.sig);
+    let call = if item.sig.unsafety.is_some() {
+        // The block may end up inside another `unsafe` block, eg. for `memop`.
+        quote! {
+            {
+                #[allow(unused_unsafe)]
+                let value = unsafe { #name(#(#names),*) };
+                value
+            }
+        }
+    } else {
+        quote! { #name(#(#names),*) }
+    };
+    // The compiler checks that the function really returns the type given with `as`, whose tokens point to the `as` option.
+    let call = match &options.as_type {
+        Some(as_type) => quote! {
+            {
+                let value: #as_type = #call;
+                value
+            }
+        },
+        None => call,
+    };
+    if options.manual.is_some() {
+        let generated = quote! {
+            #item
+            #visibility fn #wrapper_fn_name(#(_: #types),*) -> Option<(String, u8, u64, u64)> {
+                None
+            }
+            #[allow(dead_code)]
+            #visibility fn #unchanged_fn_name() -> bool {
+                true
+            }
+        };
+        return generated.into();
+    }
+    let method = match Method::of(output_type, options.mode) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    // In reproducible builds the keys of every JSON object are sorted, so that a `HashMap` is recorded the same way on every run.
+    let json = |value| {
+        if constany_common::reproducible() {
+            quote! {
+                {
+                    fn sorted(value: ::serde_json::Value) -> ::serde_json::Value {
+                        match value {
+                            ::serde_json::Value::Object(i) => {
+                                let mut entries: Vec<_> = i.into_iter().map(|(k, v)| (k, sorted(v))).collect();
+                                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                                ::serde_json::Value::Object(entries.into_iter().collect())
+                            }
+                            ::serde_json::Value::Array(i) => ::serde_json::Value::Array(i.into_iter().map(sorted).collect()),
+                            i => i,
+                        }
+                    }
+                    ::serde_json::to_value(#value).and_then(|i| ::serde_json::to_string(&sorted(i)))
                 }
+            }
+        } else {
+            quote! { ::serde_json::to_string(#value) }
+        }
+    };
+    let generation_method = match method {
+        // Sorted, so that the record does not depend on the order of a `HashSet`.
+        Method::Debug if options.mode == Some(constany_common::Mode::Phf) => quote! {
+            {
+                let mut keys: Vec<&'static str> = #call.iter().copied().collect();
+                keys.sort_unstable();
+                format!("{:?}", keys)
+            }
+        },
+        Method::Debug => {
+            // A record pasted with `include` is not rebuilt, so floats keep their decimal form there.
+            let captured = if options.include {
+                call.clone()
             } else {
-                false
+                rebuild::capture(output_type, call.clone())
+            };
+            // Both forms are read back the same way: pretty printing only adds line breaks and trailing commas.
+            let format = if options.debug_pretty {
+                "{:#?}"
+            } else {
+                "{:?}"
+            };
+            quote! {
+                format!(#format, #captured)
             }
         }
-        syn::Type::Array(i) => is_primitive_type(&i.elem),
-        syn::Type::Group(i) => is_primitive_type(&i.elem),
-        syn::Type::Slice(i) => is_primitive_type(&i.elem),
-        syn::Type::Tuple(i) => {
-            for i in &i.elems {
-                if !is_primitive_type(&i) {
-                    return false;
-                }
+        // Bytes are recorded as hex so that stage two can decode them straight into a byte string literal.
+        Method::Memop => quote! {
+            unsafe {
+                std::mem::transmute::<#output_type, [u8; std::mem::size_of::<#output_type>()]>(#call)
+            }
+            .iter()
+            .map(|i| format!("{:02x}", i))
+            .collect::<String>()
+        },
+        Method::Bytes if constany_common::is_byte_vec(output_type) => quote! {
+            #call
+                .iter()
+                .map(|i| format!("{:02x}", i))
+                .collect::<String>()
+        },
+        Method::Bytes => quote! {
+            #call
+                .to_bytes_with_nul()
+                .iter()
+                .map(|i| format!("{:02x}", i))
+                .collect::<String>()
+        },
+        Method::Base64 => quote! {
+            std::convert::AsRef::<[u8]>::as_ref(&#call)
+                .iter()
+                .map(|i| format!("{:02x}", i))
+                .collect::<String>()
+        },
+        // JSON never contains a raw line break, so every element gets its own line.
+        Method::Serde if options.array_elementwise => {
+            let json = json(quote! { i });
+            quote! {
+                #call
+                    .iter()
+                    .map(|i| #json.expect("Unable to serialize the value with `serde_json`"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
             }
-            return true;
         }
-        _ => false,
-    }
-}
-
-/// Generate a constant function
-#[proc_macro_attribute]
-pub fn const_fn(attr: TokenStream, bare_item: TokenStream) -> TokenStream {
-    let item: syn::ItemFn = syn::parse(bare_item.clone()).unwrap();
-    let name = &item.sig.ident;
-    let visibility = &item.vis;
-    let output_type = &item.sig.output;
-    let wrapper_fn_name = quote::format_ident!("_{}_wrapper_fn", name.to_string());
-    let (generation_method, fbyte) = if match output_type {
-        syn::ReturnType::Default => {
-            return syn::Error::new_spanned(
-                output_type,
-                "Fn with `()` output should not become constant",
-            )
-            .to_compile_error()
-            .into()
-        }
-        syn::ReturnType::Type(_, i) => is_primitive_type(&i),
-    } {
-        (
+        Method::Serde => {
+            let json = json(quote! { &#call });
             quote! {
-                format!("{:?}", #name())
-            },
-            0u8,
-        )
+                #json.expect("Unable to serialize the value with `serde_json`")
+            }
+        }
+    };
+    // Environment variables are the only input that can be taken away without help from the function itself. Randomness cannot be, so the function is given a seed instead, derived from its name alone.
+    let generation_method = if options.pure || constany_common::reproducible() {
+        let seed = seahash::hash(name.to_string().as_bytes()).to_string();
+        quote! {
+            {
+                // Restored when dropped, so also when the function panics.
+                struct Environment(Vec<(std::ffi::OsString, std::ffi::OsString)>);
+                impl Drop for Environment {
+                    fn drop(&mut self) {
+                        #[allow(unused_unsafe)]
+                        unsafe {
+                            std::env::remove_var(#SEED_VAR);
+                            for (key, value) in self.0.drain(..) {
+                                std::env::set_var(key, value);
+                            }
+                        }
+                    }
+                }
+                let environment = Environment(std::env::vars_os().collect());
+                #[allow(unused_unsafe)]
+                unsafe {
+                    for (key, _) in &environment.0 {
+                        std::env::remove_var(key);
+                    }
+                    std::env::set_var(#SEED_VAR, #seed);
+                }
+                #generation_method
+            }
+        }
     } else {
-        let a = quote::format_ident!("{}", name.to_string());
-        if attr.to_string().contains("memop") {
-            let output_type = match output_type {
-                syn::ReturnType::Default => unimplemented!(),
-                syn::ReturnType::Type(_, j) => j,
-            };
-            (
-                quote! {
-                    format!("{:?}", unsafe {
-                        std::mem::transmute::<#output_type, [u8; std::mem::size_of::<#output_type>()]>(#a())
-                    })
-                },
-                1,
-            )
-        } else {
-            // This should be changed.
-            (
-                quote! {
-                    format!("{:?}", #name())
-                },
-                0,
-            )
+        generation_method
+    };
+    // The resource file then only keeps the method and the hash.
+    let generation_method = if options.include {
+        let path = constany_common::include_path(&name.to_string());
+        quote! {
+            {
+                if let Err(e) = std::fs::write(#path, #generation_method) {
+                    eprintln!("constany: failed to write {}: {}", #path, e);
+                    std::process::exit(1);
+                }
+                String::new()
+            }
         }
+    } else if method == Method::Debug
+        && options.mode.is_none()
+        && options.split.is_empty()
+        && constany_common::is_primitive_type(output_type)
+        && !rebuild::has_float(output_type)
+    {
+        // A large record is included like with `include`, so that stage two does not lex it. Floats are recorded by their bits, so their record goes through stage two, which rebuilds them.
+        let path = constany_common::include_path(&name.to_string());
+        let name = name.to_string();
+        quote! {
+            {
+                let record = #generation_method;
+                let limit = std::env::var(#INCLUDE_VAR)
+                    .ok()
+                    .and_then(|i| i.parse::<usize>().ok())
+                    .unwrap_or(#DEFAULT_INCLUDE_ABOVE);
+                if record.len() > limit {
+                    if let Err(e) = std::fs::write(#path, &record) {
+                        eprintln!("constany: failed to write {}: {}", #path, e);
+                        std::process::exit(1);
+                    }
+                    eprintln!(
+                        "constany: note: `{}` hard-codes {} bytes, more than the {} of `{}`, so stage two includes it from {}",
+                        #name, record.len(), limit, #INCLUDE_VAR, #path
+                    );
+                    String::new()
+                } else {
+                    let _ = std::fs::remove_file(#path);
+                    record
+                }
+            }
+        }
+    } else {
+        generation_method
     };
-    let code_hash = seahash::hash(bare_item.to_string().as_bytes());
+    let fbyte = method as u8;
+    let code_hash = match constany_common::source_hash(&bare_item.to_string(), &options) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let type_hash = constany_common::type_hash(output_type);
+    let dependencies = constany_common::track_dependencies(&options);
+    // Only the header is read, so that checking a large resource stays cheap.
+    let resource_path = constany_common::resource_path(&name.to_string());
+    let header_len = constany_common::HEADER_LEN;
+    let features = constany_common::feature_hash(&options);
+    let included = if options.include {
+        let path = constany_common::include_path(&name.to_string());
+        quote! { && std::path::Path::new(#path).exists() }
+    } else {
+        quote! {}
+    };
+    let mut notes = Vec::new();
+    if looks_const {
+        notes.push(format!(
+            "constany: warning: `{}` looks simple enough to be a `const fn`, which needs neither stage. Mark it `const fn` and remove `const_fn` if it compiles",
+            name
+        ));
+    }
+    // The macro cannot see the `repr` of the type, so it can only warn about it.
+    if method == Method::Memop {
+        notes.push(format!(
+            "constany: warning: `{}` is hard-coded with `memop`, which copies the bytes of `{}` as they are. Rust only guarantees the layout of primitive types and `#[repr(C)]` or `#[repr(transparent)]` types, so another build may order the fields differently and read garbage. Make sure every type inside it has such a `repr` and owns no heap memory, or prefer `lazy_json` or `debug_pub`",
+            name,
+            quote!(#output_type).to_string().replace(' ', "")
+        ));
+    }
     let generated = quote! {
         #item
-        #visibility fn #wrapper_fn_name() -> (String, u8, u64) {
-            (#generation_method, #fbyte, #code_hash)
+        #dependencies
+        #visibility fn #wrapper_fn_name(#(#names: #types),*) -> Option<(String, u8, u64, u64)> {
+            #(eprintln!(#notes);)*
+            Some((#generation_method, #fbyte, #code_hash ^ #features, #type_hash))
+        }
+        #[allow(dead_code)]
+        #visibility fn #unchanged_fn_name() -> bool {
+            use std::io::Read;
+            let mut header = [0u8; #header_len];
+            std::fs::File::open(#resource_path)
+                .and_then(|mut i| i.read_exact(&mut header))
+                .is_ok()
+                && header[0] == #fbyte
+                && header[1..9] == (#code_hash ^ #features).to_be_bytes()
+                && header[9..] == #type_hash.to_be_bytes()
+                #included
         }
     };
     generated.into()
 }
 
+/// An entry in the list of `main_fn`.
+enum MainFnArg {
+    /// A constant function, written as `"name"`, `name` or `name(ARGUMENT, ..)`. The name can be a path to a module: eg. `my_mod::name`.
+    /// It is followed by `as WRAPPER` when the function picks the name of its wrapper with `wrapper = "WRAPPER"`.
+    Function(syn::Path, Vec<syn::Expr>, Option<syn::Ident>),
+    RunMain,
+    Quiet,
+    Incremental,
+    /// `inspect = HOOK`, a function called with the name and the record of each constant function before it is written.
+    Inspect(syn::Path),
+}
+
+impl Parse for MainFnArg {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let (name, arguments) = if input.peek(syn::LitStr) {
+            let name: syn::LitStr = input.parse()?;
+            match name.parse_with(syn::Path::parse_mod_style) {
+                Ok(i) => (i, Vec::new()),
+                Err(_) => {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        "Expected the name of a constant function",
+                    ))
+                }
+            }
+        } else {
+            let name = syn::Path::parse_mod_style(input)?;
+            if name.is_ident("run_main") {
+                return Ok(MainFnArg::RunMain);
+            } else if name.is_ident("quiet") {
+                return Ok(MainFnArg::Quiet);
+            } else if name.is_ident("incremental") {
+                return Ok(MainFnArg::Incremental);
+            } else if name.is_ident("inspect") && input.peek(syn::Token![=]) {
+                input.parse::<syn::Token![=]>()?;
+                return Ok(MainFnArg::Inspect(input.parse()?));
+            } else if input.peek(syn::token::Paren) {
+                let content;
+                syn::parenthesized!(content in input);
+                let arguments =
+                    Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated(&content)?;
+                for i in &arguments {
+                    if !is_constant_argument(i) {
+                        return Err(syn::Error::new_spanned(
+                            i,
+                            "Only literals, constants and statics can be passed to a constant function, so that its value does not depend on when stage one runs",
+                        ));
+                    }
+                }
+                (name, arguments.into_iter().collect())
+            } else {
+                (name, Vec::new())
+            }
+        };
+        let wrapper = if input.peek(syn::Token![as]) {
+            input.parse::<syn::Token![as]>()?;
+            Some(input.parse()?)
+        } else {
+            None
+        };
+        Ok(MainFnArg::Function(name, arguments, wrapper))
+    }
+}
+
+/// Whether an argument is known before stage one runs: a literal or the path of a constant or static, possibly borrowed, negated, or in an array or tuple.
+fn is_constant_argument(input: &syn::Expr) -> bool {
+    match input {
+        syn::Expr::Lit(_) | syn::Expr::Path(_) => true,
+        syn::Expr::Reference(i) => i.mutability.is_none() && is_constant_argument(&i.expr),
+        syn::Expr::Unary(i) => matches!(i.op, syn::UnOp::Neg(_)) && is_constant_argument(&i.expr),
+        syn::Expr::Paren(i) => is_constant_argument(&i.expr),
+        syn::Expr::Group(i) => is_constant_argument(&i.expr),
+        syn::Expr::Array(i) => i.elems.iter().all(is_constant_argument),
+        syn::Expr::Tuple(i) => i.elems.iter().all(is_constant_argument),
+        _ => false,
+    }
+}
+
+/// Environment variable naming the only function to evaluate, when `main_fn` is `quiet`.
+const QUIET_VAR: &str = "CONSTANY_QUIET_FN";
+
+/// Environment variable asking stage one to print how long each constant function took, when set to `1`.
+const TIMING_VAR: &str = "CONSTANY_TIMING";
+
+/// Environment variable holding the seed of a `pure` function while stage one evaluates it: a `u64` derived from the name of the function, the same on every machine.
+const SEED_VAR: &str = "CONSTANY_SEED";
+
+/// Environment variable setting the size of a record (in bytes) above which stage two includes it from a file instead of lexing it.
+const INCLUDE_VAR: &str = "CONSTANY_INCLUDE_ABOVE";
+
+/// Default for `CONSTANY_INCLUDE_ABOVE`: 1 MiB.
+const DEFAULT_INCLUDE_ABOVE: usize = 1 << 20;
+
 /// Attribute appending on `fn main()`
 ///
-/// When generating a constant function, you need to include it in the attribute: eg. `#[main_fn(a_constant_function, another_constant_function)]`
+/// When generating a constant function, you need to include it in the attribute: eg. `#[main_fn(a_constant_function, another_constant_function)]`. Names can also be written as strings: eg. `#[main_fn("a_constant_function")]`.
+///
+/// The list cannot be empty, unless it has `run_main`: stage one would then record nothing for stage two.
+///
+/// A constant function in another module is listed with its path: eg. `#[main_fn(my_mod::a_constant_function)]`. Its name still needs to be unique in the crate, since its resource is named after it.
+///
+/// A constant function taking parameters is listed with the arguments to evaluate it with: eg. `#[main_fn(table(&CONFIG))]`. Only literals, constants and statics are accepted.
+///
+/// Adding `run_main` to the list will run the original `main` after every constant function is evaluated, so that `const_expr!` inside it can be recorded.
+///
+/// Adding `quiet` to the list will evaluate every constant function in its own process and hide what it prints, unless it fails: the output of a failed function is shown with its name in front of each line.
+///
+/// Adding `incremental` to the list will skip every constant function whose resource was recorded from the same source, so that only edited functions are evaluated again. Functions called with arguments are always evaluated, since the arguments are not part of the source.
+///
+/// Adding `inspect = a_hook` to the list will call `a_hook(name, record)` before writing the record of each constant function, eg. to log it or check invariants.
+/// The hook takes two `&str` and returns a `Result<(), E>` of any `E: Display`. When it returns an error or panics, the record is not written and stage one fails.
+///
+/// Running stage one with `CONSTANY_TIMING=1` prints how long each constant function took to stderr, to find which ones are worth hard-coding.
 #[proc_macro_attribute]
 pub fn main_fn(attr: TokenStream, item: TokenStream) -> TokenStream {
-    drop(item);
-    let mut attr_vec: Vec<String> = Vec::new();
-    for i in attr {
-        let i: proc_macro::TokenStream = i.into();
-        attr_vec.push(i.to_string())
+    let args = match Punctuated::<MainFnArg, syn::Token![,]>::parse_terminated.parse(attr) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let item: syn::ItemFn = syn::parse(item).unwrap();
+    if let Err(e) = constany_common::check_single_stage(&item, "main_fn") {
+        return e.to_compile_error().into();
     }
     let mut fn_vec = Vec::new();
-    for i in attr_vec {
-        if i != "," {
-            let fn_name = &i[1..i.len() - 1];
-            let wrapper_fn_name = quote::format_ident!("_{}_wrapper_fn", fn_name.to_string());
-            fn_vec.push((wrapper_fn_name, format!("target/{}.res", fn_name)));
+    let mut run_main = false;
+    let mut quiet = false;
+    let mut incremental = false;
+    let mut inspect = None;
+    let mut names = std::collections::HashSet::new();
+    for i in args {
+        match i {
+            MainFnArg::Function(fn_path, arguments, wrapper) => {
+                // The resource is named after the function alone, since stage two does not know its module.
+                let name = fn_path.segments.last().unwrap().ident.to_string();
+                // A second entry would silently replace the record of the first one.
+                if !names.insert(name.clone()) {
+                    return syn::Error::new_spanned(
+                        &fn_path,
+                        format!(
+                            "`{}` is listed more than once in `main_fn`, but stage two only has one value for it, recorded in a resource named after the function: list it once",
+                            name
+                        ),
+                    )
+                    .to_compile_error()
+                    .into();
+                }
+                let sibling = |ident: syn::Ident| {
+                    let mut sibling = fn_path.clone();
+                    sibling.segments.last_mut().unwrap().ident = ident;
+                    sibling
+                };
+                let (wrapper_fn_name, unchanged_fn_name) =
+                    constany_common::wrapper_names(&name, wrapper.as_ref());
+                let wrapper_fn_name = sibling(wrapper_fn_name);
+                let unchanged_fn_name = sibling(unchanged_fn_name);
+                let path = constany_common::resource_path(&name);
+                let fn_name = quote!(#fn_path).to_string().replace(' ', "");
+                // Stage two can call a function at the root of the crate by its name alone.
+                let shareable = arguments.is_empty()
+                    && fn_path.leading_colon.is_none()
+                    && fn_path.segments.len() == 1;
+                fn_vec.push((
+                    fn_name,
+                    wrapper_fn_name,
+                    path,
+                    arguments,
+                    unchanged_fn_name,
+                    shareable,
+                ));
+            }
+            MainFnArg::RunMain => run_main = true,
+            MainFnArg::Quiet => quiet = true,
+            MainFnArg::Incremental => incremental = true,
+            MainFnArg::Inspect(hook) => inspect = Some(hook),
         }
     }
-    let mut generated = quote! {};
-    for i in fn_vec {
-        let (i, j) = i;
+    // Stage two would then find no resource at all, so an empty list is most likely a mistake.
+    if fn_vec.is_empty() && !run_main {
+        return syn::Error::new(
+            proc_macro::Span::call_site().into(),
+            "`main_fn` lists no constant function, so stage one would record nothing: add their names, eg. `#[main_fn(my_function)]`, or `run_main` to record `const_expr!`",
+        )
+        .to_compile_error()
+        .into();
+    }
+    // Functions are evaluated in the order of their names, so that what stage one prints does not depend on the list.
+    fn_vec.sort_by(|a, b| a.0.cmp(&b.0));
+    let resource_dir = constany_common::resource_dir();
+    let sharing = fn_vec.iter().any(|i| i.5);
+    let mut generated = quote! {
+        if let Err(e) = std::fs::create_dir_all(#resource_dir) {
+            eprintln!("constany: failed to create {}: {}", #resource_dir, e);
+            std::process::exit(1);
+        }
+        let mut failed = false;
+        let timing = std::env::var(#TIMING_VAR).is_ok_and(|i| i == "1");
+    };
+    if quiet {
+        generated = quote! {
+            #generated
+            let evaluated = std::env::var(#QUIET_VAR).ok();
+        };
+    }
+    // The first function recording each string, by its value.
+    if sharing {
         generated = quote! {
             #generated
-            let (j, i, k) = #i();
+            let mut shared = std::collections::HashMap::new();
+        };
+    }
+    let debug = Method::Debug as u8;
+    let str_type = constany_common::type_hash(&syn::parse_quote!(&'static str));
+    let shared_prefix = constany_common::SHARED_PREFIX;
+    for (fn_name, i, j, arguments, unchanged, shareable) in fn_vec {
+        // A string already recorded by a function evaluated before only names it, and stage two calls that function instead of repeating the literal.
+        let share = if shareable {
+            quote! {
+                let j = if i == #debug && l == #str_type && !j.is_empty() {
+                    match shared.get(&j) {
+                        Some(first) => format!("{}{}\n{}", #shared_prefix, first, j),
+                        None => {
+                            shared.insert(j.clone(), #fn_name);
+                            j
+                        }
+                    }
+                } else {
+                    j
+                };
+            }
+        } else {
+            quote! {}
+        };
+        let record = quote! {
+            #share
             let mut constructed = vec![i];
             constructed.extend_from_slice(&k.to_be_bytes());
+            constructed.extend_from_slice(&l.to_be_bytes());
             constructed.extend_from_slice(&j.into_bytes());
-            std::fs::write(#j, constructed).unwrap();
+            // Renaming replaces the file at once, so a build running at the same time never reads half of it.
+            let temporary = format!("{}.{}.tmp", #j, std::process::id());
+            if let Err(e) = std::fs::write(&temporary, constructed)
+                .and_then(|_| std::fs::rename(&temporary, #j))
+            {
+                eprintln!("constany: failed to write {}: {}", #j, e);
+                failed = true;
+            }
+        };
+        // A rejected record is not written, and the old one is removed so that stage two does not build with it.
+        let record = match &inspect {
+            Some(hook) => {
+                let hook_name = quote!(#hook).to_string().replace(' ', "");
+                quote! {
+                    let verdict = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| #hook(#fn_name, &j)));
+                    let accepted = match verdict {
+                        Ok(Ok(())) => true,
+                        Ok(Err(e)) => {
+                            eprintln!("constany: {} rejected the value of {}: {}", #hook_name, #fn_name, e);
+                            false
+                        }
+                        Err(_) => {
+                            eprintln!("constany: {} panicked on the value of {}", #hook_name, #fn_name);
+                            false
+                        }
+                    };
+                    if accepted {
+                        #record
+                    } else {
+                        let _ = std::fs::remove_file(#j);
+                        failed = true;
+                    }
+                }
+            }
+            None => record,
+        };
+        let report = quote! {
+            if timing {
+                eprintln!("constany: {} took {:?}", #fn_name, started.elapsed());
+            }
+        };
+        // Functions with a `manual` value have nothing to record.
+        // A panic is caught so that the other functions are still evaluated. The panic itself is printed by the default hook.
+        let write = quote! {
+            let started = std::time::Instant::now();
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| #i(#(#arguments),*)));
+            #report
+            match result {
+                Ok(Some((j, i, k, l))) => {
+                    #record
+                }
+                Ok(None) => {}
+                Err(_) => {
+                    eprintln!("constany: {} panicked, so it was not evaluated", #fn_name);
+                    // An empty resource tells stage two why there is no value, instead of leaving a stale one.
+                    let _ = std::fs::write(#j, []);
+                    failed = true;
+                }
+            }
+        };
+        // The process evaluates itself again for a single function, so that its output can be captured without platform-specific redirection.
+        // The time is then measured around the whole process, since what it prints is hidden.
+        let write = if quiet {
+            quote! {
+                match &evaluated {
+                    None => {
+                        let started = std::time::Instant::now();
+                        match std::env::current_exe().and_then(|exe| {
+                            std::process::Command::new(exe)
+                                .env(#QUIET_VAR, #fn_name)
+                                .env_remove(#TIMING_VAR)
+                                .output()
+                        }) {
+                            Ok(output) if output.status.success() => {}
+                            Ok(output) => {
+                                let stdout = String::from_utf8_lossy(&output.stdout);
+                                let stderr = String::from_utf8_lossy(&output.stderr);
+                                for line in stdout.lines().chain(stderr.lines()) {
+                                    eprintln!("[{}] {}", #fn_name, line);
+                                }
+                                failed = true;
+                            }
+                            Err(e) => {
+                                eprintln!("constany: failed to evaluate {}: {}", #fn_name, e);
+                                failed = true;
+                            }
+                        }
+                        #report
+                    }
+                    Some(evaluated) if evaluated == #fn_name => {
+                        #write
+                    }
+                    Some(_) => {}
+                }
+            }
+        } else {
+            write
+        };
+        // A function is skipped when its resource starts with the method and the hash it would record.
+        // The hash cannot cover the arguments, nor what they point to, so a function called with arguments is always evaluated.
+        let write = if incremental && arguments.is_empty() {
+            quote! {
+                if !#unchanged() {
+                    #write
+                }
+            }
+        } else {
+            write
+        };
+        generated = quote! {
+            #generated
+            {
+                #write
+            }
         }
     }
-    //let item: syn::ItemFn = syn::parse(item).unwrap();
     let generated = quote! {
-        fn main() {
+        #generated
+        if failed {
+            std::process::exit(1);
+        }
+    };
+    let generated = if quiet {
+        quote! {
             #generated
+            if evaluated.is_some() {
+                std::process::exit(0);
+            }
+        }
+    } else {
+        generated
+    };
+    let generated = if run_main {
+        let mut item = item;
+        let output_type = item.sig.output.clone();
+        item.sig.ident = quote::format_ident!("_original_main_fn");
+        quote! {
+            fn main() #output_type {
+                // In a block of its own, so that its variables do not shadow the items used by `main`.
+                {
+                    #generated
+                }
+                #item
+                _original_main_fn()
+            }
+        }
+    } else {
+        quote! {
+            fn main() {
+                #generated
+            }
         }
     };
     generated.into()
 }
+
+/// Evaluate an expression in stage one, and hard-code its value in stage two: eg. `let a = constany::const_expr!(add_one_to_six());`
+///
+/// The value is recorded when stage one evaluates the expression, so it needs to be reached by `main` (see `run_main` in `main_fn`) or by a constant function.
+/// Only values whose `Debug` output is a valid literal (primitive types, arrays and tuples of them) can be hard-coded.
+#[proc_macro]
+pub fn const_expr(item: TokenStream) -> TokenStream {
+    let expr: syn::Expr = match syn::parse(item.clone()) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let code_hash = seahash::hash(item.to_string().as_bytes());
+    let path = constany_common::resource_path(&constany_common::expr_resource_name(code_hash));
+    let resource_dir = constany_common::resource_dir();
+    let fbyte = Method::Debug as u8;
+    let generated = quote! {
+        {
+            let value = #expr;
+            fn type_name_of<T>(_: &T) -> &'static str {
+                std::any::type_name::<T>()
+            }
+            let mut constructed = vec![#fbyte];
+            constructed.extend_from_slice(&#code_hash.to_be_bytes());
+            // An expression has no declared type: the name of its type is in the record instead.
+            constructed.extend_from_slice(&0u64.to_be_bytes());
+            constructed.extend_from_slice(format!("{}\n{:?}", type_name_of(&value), value).as_bytes());
+            if let Err(e) = std::fs::create_dir_all(#resource_dir)
+                .and_then(|_| std::fs::write(#path, constructed))
+            {
+                eprintln!("constany: failed to write {}: {}", #path, e);
+                std::process::exit(1);
+            }
+            value
+        }
+    };
+    generated.into()
+}
+
+/// Test that the hard-coded value of a constant function is still the value of the original function: eg. `constany::assert_baked!(table(&CONFIG, 2));`
+///
+/// In stage two, this adds a `#[test]` calling both the generated function and the original one, which stage two keeps for tests, with the given arguments. Run it with `cargo test` and the stage two feature.
+/// The value needs to implement `PartialEq` and `Debug`. The macro has to be in the module of the function, so that both functions are in scope.
+/// In stage one, it expands to nothing.
+#[proc_macro]
+pub fn assert_baked(_: TokenStream) -> TokenStream {
+    TokenStream::new()
+}
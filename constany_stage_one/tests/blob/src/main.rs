@@ -0,0 +1,19 @@
+#[cfg(any(
+    not(any(feature = "stage_one", feature = "stage_two")),
+    all(feature = "stage_two", feature = "stage_one")
+))]
+use constany_blank as constany;
+#[cfg(all(feature = "stage_one", not(feature = "stage_two")))]
+use constany_stage_one as constany;
+#[cfg(all(feature = "stage_two", not(feature = "stage_one")))]
+use constany_stage_two as constany;
+
+#[constany::main_fn(blob)]
+fn main() {
+    println!("{}", blob().iter().map(|i| u32::from(*i)).sum::<u32>());
+}
+
+#[constany::const_fn]
+fn blob() -> Vec<u8> {
+    (0..1 << 20).map(|i: u32| (i ^ (i >> 8)) as u8).collect()
+}
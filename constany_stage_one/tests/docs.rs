@@ -0,0 +1,11 @@
+/// `docs/index.md` is generated from `readme.md` by `docs/generate.sh`.
+#[test]
+fn docs_match_readme() {
+    let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+    let readme = std::fs::read_to_string(root.join("readme.md")).unwrap();
+    let docs = std::fs::read_to_string(root.join("docs/index.md")).unwrap();
+    assert!(
+        readme == docs,
+        "docs/index.md differs from readme.md. Please run `sh docs/generate.sh`"
+    );
+}
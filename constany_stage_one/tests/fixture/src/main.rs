@@ -0,0 +1,764 @@
+#[cfg(any(
+    not(any(feature = "stage_one", feature = "stage_two")),
+    all(feature = "stage_two", feature = "stage_one")
+))]
+use constany_blank as constany;
+#[cfg(all(feature = "stage_one", not(feature = "stage_two")))]
+use constany_stage_one as constany;
+#[cfg(all(feature = "stage_two", not(feature = "stage_one")))]
+use constany_stage_two as constany;
+
+#[constany::main_fn(
+    sum, chars, floats, text, list, maybe, boxed, cname, point, pair, primes, built, red, green, blue, squares, matrix, cube, keywords, nothing, limit, greeting, wide, icon, root, table(&CONFIG, 2), step, powers, sieve, fragile, poem, layout, labels, boiling, unit, window, letters, shared, title, motto, owner, profile, scores, headers, blob, raw, answer, banner, seed, evens, lengths, vowels, initials, tripled, hash, level, wrapped, checksum, units::kilo, units::imperial::yard, distance_unit, noise, stride, offset, frame, compact_frame, ordinals, tally as evaluate_tally, settings, no_settings, widened, float_bits, small_primes, hermetic, package, assembled, part, constany_fixture_exported, motd, welcome, nan_payload, measured(EMPTY), corner, units::origin, slogan, catalog, seeded, run_main, incremental, inspect = check_record
+)]
+fn main() {
+    let expr = constany::const_expr!((1..10).map(|i: u64| i * i).sum::<u64>());
+    let min = constany::const_expr!(i128::MIN);
+    println!("{} {}", SUM, PRIMES);
+    println!("{:?}", chars());
+    println!("{:?}", floats());
+    println!("{:?} {:?} {:?}", text(), list(), maybe());
+    println!("{:?} {:?}", boxed(), cname());
+    println!("{:?} {:?}", point(), pair());
+    println!("{:?} {}", frame(), frame() == compact_frame());
+    println!("{} {} {}", ordinals().len(), ordinals()[0], ordinals()[39]);
+    println!("{}", tally());
+    #[cfg(feature = "stage_two")]
+    println!("{:?} {:?} {:?}", SETTINGS, settings(), no_settings());
+    println!("{}", widened());
+    println!("{:?}", float_bits().map(f64::to_bits));
+    #[cfg(feature = "stage_two")]
+    {
+        let mut doubled = [0; SMALL_PRIMES_LEN];
+        for (i, j) in doubled.iter_mut().zip(small_primes()) {
+            *i = j * 2;
+        }
+        println!("{} {:?}", SMALL_PRIMES_LEN, doubled);
+    }
+    println!("{:?}", built().duration_since(std::time::UNIX_EPOCH));
+    println!("{:?} {:?} {:?}", red(), green(), blue());
+    println!("{:?}", squares());
+    println!("{:?} {:?}", matrix(), cube());
+    println!("{:?} {:?} {:?}", KEYWORDS, keywords(), nothing());
+    #[cfg(feature = "stage_two")]
+    println!("{:?} {:?} {} {}", LIMIT, limit(), GREETING, greeting());
+    println!("{}", expr);
+    println!("{:?} {}", wide(), min);
+    println!("{:?} {:?}", icon(), root());
+    println!("{:?} {}", table(&CONFIG, 2), step()(21));
+    println!(
+        "{} {} {}",
+        powers().len(),
+        powers()[1023],
+        std::ptr::eq(powers(), powers())
+    );
+    println!("{:?} {}", sieve(), fragile());
+    let layout = layout();
+    println!("{:?} {} {}", poem(), layout.width, layout.height);
+    println!("{:?}", labels());
+    println!("{} {:?}", boiling(), unit());
+    let mut exhausted = letters();
+    exhausted.by_ref().for_each(drop);
+    println!("{:?} {:?} {:?}", window(), letters(), exhausted.is_empty());
+    println!("{:?} {:?} {:?} {:?}", shared(), title(), motto(), owner());
+    println!("{:?}", profile());
+    println!("{:?} {:?}", scores(), headers());
+    println!("{:?} {}", blob(), unsafe { raw() });
+    // Only compiles if the generated function keeps its ABI.
+    let answer: extern "C" fn() -> u32 = answer;
+    let banner: &'static str = banner();
+    println!("{} {} {}", answer(), banner, BANNER);
+    println!("{:?}", seed());
+    println!("{:?} {:?} {:?} {:?}", evens(), lengths(), vowels(), initials());
+    println!("{} {} {} {}", tripled(), hash(), level(), wrapped());
+    #[cfg(feature = "stage_two")]
+    println!("{:?} {} {} {:?}", TABLE, CHECKSUM, LEN, checksum());
+    println!(
+        "{} {} {}",
+        units::kilometres(5000),
+        units::yards(360),
+        distance_unit()
+    );
+    println!("{} {:?}", noise().len(), &noise()[..4]);
+    #[cfg(feature = "stage_two")]
+    println!("{} {} {}", STRIDE, stride(), offset());
+    println!("{} {:?}", hermetic(), package());
+    println!("{} {}", assembled(), part());
+    println!("{}", unsafe { linked() });
+    println!("{} {}", motd(), welcome());
+    println!("{:x?}", nan_payload().map(f32::to_bits));
+    println!("{}", measured(EMPTY));
+    println!("{:?} {:?}", corner(), units::origin());
+    println!("{}", slogan());
+    println!("{} {}", catalog().len(), catalog()["k7"]);
+    println!("{}", seeded());
+}
+
+// Only links if the generated function keeps `#[no_mangle]`.
+extern "C" {
+    #[link_name = "constany_fixture_exported"]
+    fn linked() -> u32;
+}
+
+#[cfg(feature = "stage_two")]
+const SUM: u8 = sum();
+#[cfg(not(feature = "stage_two"))]
+const SUM: u8 = 0;
+#[cfg(feature = "stage_two")]
+const KEYWORDS: &[&str] = keywords();
+#[cfg(not(feature = "stage_two"))]
+const KEYWORDS: &[&str] = &[];
+#[cfg(feature = "stage_two")]
+const PRIMES: usize = primes();
+#[cfg(not(feature = "stage_two"))]
+const PRIMES: usize = 0;
+#[cfg(feature = "stage_two")]
+const BANNER: &str = banner();
+#[cfg(not(feature = "stage_two"))]
+const BANNER: &str = "";
+
+#[constany::const_fn]
+fn sum() -> u8 {
+    (1..7).sum()
+}
+
+#[constany::const_fn(len_only)]
+fn primes() -> Vec<u32> {
+    (2..1000u32)
+        .filter(|i| (2..*i).all(|j| i % j != 0))
+        .collect()
+}
+
+#[constany::const_fn]
+fn built() -> std::time::SystemTime {
+    std::time::UNIX_EPOCH + std::time::Duration::new(1_600_000_000, 5)
+}
+
+#[constany::const_fn(include)]
+fn squares() -> [(u32, &'static str); 3] {
+    [(1, "one"), (4, "four"), (9, "nine")]
+}
+
+#[constany::const_fn]
+fn matrix() -> [[u8; 3]; 2] {
+    [[1, 2, 3], [4, 5, 6]]
+}
+
+#[constany::const_fn]
+fn cube() -> [[[f32; 2]; 2]; 2] {
+    [[[0.5, f32::NAN], [-1.0, 2.0]], [[3.0, f32::INFINITY], [4.0, 5.5]]]
+}
+
+#[constany::const_fn]
+fn keywords() -> &'static [&'static str] {
+    Box::leak(vec!["fn", "\"quoted\"", "tab\t", "é"].into_boxed_slice())
+}
+
+#[constany::const_fn]
+fn nothing() -> &'static [u8] {
+    &[]
+}
+
+#[constany::const_fn(emit = "const_item")]
+pub fn limit() -> Option<u32> {
+    Some(40 + 2)
+}
+
+#[constany::const_fn(emit = "static_item")]
+fn greeting() -> &'static str {
+    "hi"
+}
+
+#[constany::const_fn(golden = "golden/wide.txt")]
+fn wide() -> (u128, [i128; 2], i8) {
+    (u128::MAX, [i128::MIN, i128::MAX], i8::MIN)
+}
+
+#[constany::const_fn]
+fn icon() -> Option<std::path::PathBuf> {
+    Some(std::path::Path::new("assets").join("icon \"1\".png"))
+}
+
+#[constany::const_fn]
+fn root() -> &'static std::path::Path {
+    std::path::Path::new("/")
+}
+
+#[constany::const_fn]
+fn chars() -> ([char; 4], [bool; 2]) {
+    (['a', '\'', '\u{301}', '🦀'], [true, false])
+}
+
+#[constany::const_fn]
+fn floats() -> (f32, [f64; 3]) {
+    (f32::NAN, [f64::INFINITY, -f64::INFINITY, 0.5])
+}
+
+#[constany::const_fn]
+fn text() -> String {
+    "a\"b".repeat(2)
+}
+
+#[constany::const_fn]
+fn list() -> Vec<std::net::Ipv4Addr> {
+    vec![std::net::Ipv4Addr::LOCALHOST]
+}
+
+#[constany::const_fn]
+fn maybe() -> Option<Result<&'static str, u8>> {
+    Some(Err(3))
+}
+
+#[constany::const_fn]
+fn boxed() -> Box<[u16]> {
+    (1..5).collect()
+}
+
+#[constany::const_fn]
+fn cname() -> &'static std::ffi::CStr {
+    std::ffi::CStr::from_bytes_with_nul(b"lib7\0").unwrap()
+}
+
+mod shapes {
+    #[derive(Debug)]
+    pub struct Point {
+        pub x: i32,
+        pub y: (f32, bool),
+    }
+}
+
+#[constany::const_fn(debug_pub, force_const)]
+fn point() -> shapes::Point {
+    shapes::Point {
+        x: -1,
+        y: (0.5, true),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Frame {
+    origin: (i32, i32),
+    corners: [[u8; 2]; 2],
+    label: &'static str,
+}
+
+fn build_frame() -> Frame {
+    Frame {
+        origin: (-3, 4),
+        corners: [[0, 1], [2, 3]],
+        label: "a \"frame\",\n",
+    }
+}
+
+#[constany::const_fn(debug_pub, debug_pretty)]
+fn frame() -> Frame {
+    build_frame()
+}
+
+#[constany::const_fn(debug_pub)]
+fn compact_frame() -> Frame {
+    build_frame()
+}
+
+mod colors {
+    #[derive(Debug)]
+    pub enum Color {
+        Red,
+        Green,
+        Blue,
+    }
+}
+
+#[constany::const_fn(debug_pub)]
+fn red() -> colors::Color {
+    colors::Color::Red
+}
+
+#[constany::const_fn(debug_pub)]
+fn green() -> colors::Color {
+    colors::Color::Green
+}
+
+#[constany::const_fn(debug_pub)]
+fn blue() -> colors::Color {
+    colors::Color::Blue
+}
+
+#[constany::const_fn(debug_pub)]
+fn corner() -> self::colors::Color {
+    self::colors::Color::Green
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct Pair {
+    a: u32,
+    b: [[u16; 2]; 2],
+}
+
+#[constany::const_fn(memop)]
+fn pair() -> Pair {
+    Pair { a: 7, b: [[1, 2], [3, 4]] }
+}
+
+pub struct Config {
+    shift: u32,
+}
+
+const CONFIG: Config = Config { shift: 3 };
+
+#[constany::const_fn]
+fn table(config: &Config, scale: u32) -> [u32; 4] {
+    let mut table = [0; 4];
+    for (i, j) in table.iter_mut().enumerate() {
+        *j = ((i as u32) << config.shift) * scale;
+    }
+    table
+}
+
+fn double(i: u32) -> u32 {
+    i * 2
+}
+
+#[constany::const_fn(manual = "src/step.rs")]
+fn step() -> fn(u32) -> u32 {
+    double
+}
+
+// Only compiles if the generated function is not `const`, as `format!` is not.
+#[constany::const_fn(manual = "src/slogan.rs")]
+fn slogan() -> String {
+    format!("{} {}", "built", "once")
+}
+
+#[constany::const_fn(static_ref)]
+fn powers() -> [u64; 1024] {
+    let mut powers = [0; 1024];
+    for (i, j) in powers.iter_mut().enumerate() {
+        *j = 3u64.wrapping_pow(i as u32);
+    }
+    powers
+}
+
+#[constany::const_fn]
+fn sieve() -> &'static [u32] {
+    let mut composite = [false; 50];
+    let mut primes = Vec::new();
+    for i in 2..50 {
+        if !composite[i] {
+            primes.push(i as u32);
+            for j in (i * i..50).step_by(i) {
+                composite[j] = true;
+            }
+        }
+    }
+    primes.leak()
+}
+
+#[constany::const_fn]
+fn fragile() -> bool {
+    if std::env::var_os("FIXTURE_PANIC").is_some() {
+        panic!("asked to panic");
+    }
+    true
+}
+
+#[constany::const_fn]
+fn poem() -> String {
+    ["roses", "violets", ""].join("\n")
+}
+
+pub struct Layout {
+    width: u32,
+    height: u32,
+}
+
+// Like `{:#?}`, over several lines.
+impl std::fmt::Debug for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "Layout {{\n    width: {},\n    height: {},\n}}",
+            self.width, self.height
+        )
+    }
+}
+
+#[constany::const_fn(debug_pub)]
+fn layout() -> Layout {
+    Layout {
+        width: 640,
+        height: 480,
+    }
+}
+
+#[constany::const_fn]
+fn labels() -> Vec<Box<str>> {
+    vec!["".into(), "tab\t\"é\"".into()]
+}
+
+type Celsius = u32;
+type Unit = Option<String>;
+
+#[constany::const_fn(as = "u32")]
+fn boiling() -> Celsius {
+    100
+}
+
+#[constany::const_fn(as = "Option<String>")]
+fn unit() -> Unit {
+    Some("°C".to_string())
+}
+
+#[constany::const_fn]
+fn window() -> std::ops::Range<usize> {
+    let text = "key = value";
+    let start = text.find('=').unwrap() + 2;
+    start..text.len()
+}
+
+#[constany::const_fn]
+fn letters() -> std::ops::RangeInclusive<char> {
+    let mut letters = 'a'..='e';
+    letters.next();
+    letters
+}
+
+#[constany::const_fn]
+fn shared() -> std::sync::Arc<[u8]> {
+    (1..=4).collect()
+}
+
+#[constany::const_fn]
+fn title() -> std::rc::Rc<str> {
+    "con\"stany".into()
+}
+
+#[constany::const_fn]
+fn motto() -> std::borrow::Cow<'static, str> {
+    std::borrow::Cow::Owned("hé".repeat(2))
+}
+
+#[constany::const_fn]
+fn owner() -> Option<std::rc::Rc<String>> {
+    Some(std::rc::Rc::new(String::from("me")))
+}
+
+#[constany::const_fn(depends_env = "FIXTURE_PROFILE")]
+fn profile() -> Option<String> {
+    std::env::var("FIXTURE_PROFILE").ok()
+}
+
+#[constany::const_fn]
+fn scores() -> Vec<(&'static str, u32)> {
+    vec![("alice", 3), ("\"bob\"", 5)]
+}
+
+#[constany::const_fn]
+fn headers() -> Vec<(String, Option<u8>)> {
+    vec![(String::from("Accept"), None), ("X-".repeat(2), Some(1))]
+}
+
+#[derive(Debug)]
+pub struct Blob(Vec<u8>);
+
+impl AsRef<[u8]> for Blob {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Blob {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Blob(bytes.to_vec())
+    }
+}
+
+/// Would collide with the helper of `blob`, if stage two did not keep it in a module.
+#[allow(non_upper_case_globals, dead_code)]
+const CONST_VALUE_OF_FN_blob: u8 = 0;
+
+#[constany::const_fn(base64, from_bytes = "Blob::from_bytes")]
+fn blob() -> Blob {
+    Blob((0..=255).step_by(51).chain([62, 63]).collect())
+}
+
+/// # Safety
+///
+/// Always safe, it only reads a local through a raw pointer.
+#[constany::const_fn]
+unsafe fn raw() -> u32 {
+    let value = 7u32;
+    *std::ptr::addr_of!(value) * 6
+}
+
+#[constany::const_fn]
+pub extern "C" fn answer() -> u32 {
+    (1..=6).product::<u32>() / 720 * 42
+}
+
+/// Exported under its own name.
+#[constany::const_fn]
+#[no_mangle]
+pub extern "C" fn constany_fixture_exported() -> u32 {
+    (1..=4).product()
+}
+
+#[constany::const_fn(intern)]
+fn banner() -> String {
+    format!("{} v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+/// Reads a variable missing from `depends_env`, which only `assert_baked!` notices.
+#[constany::const_fn]
+fn seed() -> Option<String> {
+    std::env::var("FIXTURE_SEED").ok()
+}
+
+#[constany::const_fn]
+fn evens() -> Vec<u32> {
+    (0..10).filter(|i| i % 2 == 0).collect()
+}
+
+#[constany::const_fn]
+fn lengths() -> std::collections::BTreeMap<String, usize> {
+    ["const", "any", "fn"]
+        .iter()
+        .map(|i| (i.to_string(), i.len()))
+        .collect()
+}
+
+#[constany::const_fn]
+fn vowels() -> std::collections::BTreeSet<char> {
+    "constany macro".chars().filter(|i| "aeiou".contains(*i)).collect()
+}
+
+#[constany::const_fn]
+fn initials() -> String {
+    ["const", "any"].iter().filter_map(|i| i.chars().next()).collect()
+}
+
+/// Only uses what a `const fn` can, which stage one points out.
+#[constany::const_fn]
+fn tripled() -> u32 {
+    let mut value = 1;
+    while value < 100 {
+        value *= 3;
+    }
+    value
+}
+
+#[constany::const_fn]
+fn hash() -> std::num::Wrapping<u32> {
+    "constany"
+        .bytes()
+        .fold(std::num::Wrapping(2166136261), |hash, i| {
+            (hash ^ std::num::Wrapping(i as u32)) * std::num::Wrapping(16777619)
+        })
+}
+
+#[constany::const_fn]
+fn level() -> std::num::Saturating<u8> {
+    (0..100).fold(std::num::Saturating(0), |level, _| level + std::num::Saturating(3))
+}
+
+#[allow(unused_parens)]
+#[constany::const_fn]
+fn wrapped() -> (u64) {
+    (1..=20).product()
+}
+
+#[constany::const_fn(split(TABLE, CHECKSUM, LEN))]
+fn checksum() -> ([u8; 4], u32, usize) {
+    let table: Vec<u8> = (0..4).map(|i| i * 3).collect();
+    let checksum = table.iter().map(|i| *i as u32).sum();
+    let len = table.len();
+    (std::convert::TryInto::try_into(table).unwrap(), checksum, len)
+}
+
+#[constany::const_fn(depends_feature = "metric")]
+fn distance_unit() -> String {
+    let unit = if cfg!(feature = "metric") { "km" } else { "mi" };
+    unit.to_string()
+}
+
+#[constany::const_fn]
+fn noise() -> Vec<u8> {
+    (0..=255).rev().collect()
+}
+
+#[constany::const_fn(emit = "const_item")]
+fn stride() -> std::num::NonZeroU16 {
+    std::num::NonZeroU16::new((1..=4).sum()).unwrap()
+}
+
+#[constany::const_fn]
+fn offset() -> std::num::NonZero<i64> {
+    std::num::NonZero::new(-(1..=3).product::<i64>()).unwrap()
+}
+
+// `serde` alone only supports arrays of up to 32 elements.
+#[constany::const_fn(serde, array_elementwise)]
+fn ordinals() -> [String; 40] {
+    std::array::from_fn(|i| format!("#{}", i + 1))
+}
+
+// Its keys come in another order on every run, unless the build is reproducible.
+#[constany::const_fn(serde)]
+fn catalog() -> std::collections::HashMap<String, u32> {
+    (0..16).map(|i| (format!("k{}", i), i * i)).collect()
+}
+
+#[constany::const_fn(wrapper = "evaluate_tally")]
+fn tally() -> u32 {
+    (1..=5).map(|i| i * i).sum()
+}
+
+// Stage one calls it with the record of every function before writing it.
+#[allow(dead_code)]
+fn check_record(name: &str, value: &str) -> Result<(), String> {
+    if std::env::var("FIXTURE_REJECT").is_ok_and(|i| i == name) {
+        return Err(format!("asked to reject {}", value));
+    }
+    if name == "sum" && value != "21" {
+        return Err(format!("expected 21, found {}", value));
+    }
+    Ok(())
+}
+
+// Takes the name stage one would give to the wrapper of `tally`.
+#[allow(dead_code)]
+fn _tally_wrapper_fn() {}
+
+#[constany::const_fn(emit = "const_item")]
+fn settings() -> &'static [(&'static str, &'static str)] {
+    let pairs = vec![
+        ("name", env!("CARGO_PKG_NAME")),
+        ("greeting", "say \"hi\",\n"),
+        ("path", "C:\\temp"),
+    ];
+    Box::leak(pairs.into_boxed_slice())
+}
+
+#[constany::const_fn]
+fn no_settings() -> &'static [(&'static str, &'static str)] {
+    Box::leak(Vec::new().into_boxed_slice())
+}
+
+#[constany::const_fn(with_len)]
+fn small_primes() -> &'static [u32] {
+    let primes: Vec<u32> = (2..20).filter(|i| (2..*i).all(|j| i % j != 0)).collect();
+    Box::leak(primes.into_boxed_slice())
+}
+
+#[constany::const_fn]
+fn float_bits() -> [f64; 4] {
+    [
+        f64::from_bits(1),
+        f64::MIN_POSITIVE / 3.0,
+        f64::INFINITY,
+        0.1 + 0.2,
+    ]
+}
+
+#[constany::const_fn]
+fn motd() -> &'static str {
+    Box::leak(["hello", "again"].join(" ").into_boxed_str())
+}
+
+#[constany::const_fn]
+fn welcome() -> &'static str {
+    Box::leak(format!("{} again", "hello").into_boxed_str())
+}
+
+// Evaluated before `part`, which it calls.
+#[constany::const_fn]
+fn assembled() -> u32 {
+    part() * 10 + 1
+}
+
+#[constany::const_fn]
+fn part() -> u32 {
+    (1..4).sum()
+}
+
+#[constany::const_fn(pure)]
+fn hermetic() -> bool {
+    if std::path::Path::new("target/panic_hermetic").exists() {
+        panic!("asked to panic");
+    }
+    std::env::var_os("PATH").is_none()
+}
+
+// The map iterates in the same order on every run, and the seed is the same on every machine.
+#[constany::const_fn(pure)]
+fn seeded() -> String {
+    type Fixed = std::hash::BuildHasherDefault<std::collections::hash_map::DefaultHasher>;
+    // `run_main` calls it outside of stage one's evaluation, without a seed.
+    let seed: u64 = std::env::var("CONSTANY_SEED").map_or(0, |i| i.parse().unwrap());
+    let map: std::collections::HashMap<u64, (), Fixed> = (0..8).map(|i| (seed % 100 + i, ())).collect();
+    format!("{:?}", map.keys().collect::<Vec<_>>())
+}
+
+#[constany::const_fn]
+fn package() -> Option<String> {
+    std::env::var("CARGO_PKG_NAME").ok()
+}
+
+const EMPTY: String = String::new();
+
+// Only compiles in stage two if the generated function is not `const`, since it drops its argument.
+#[constany::const_fn]
+fn measured(text: String) -> usize {
+    text.len() + 3
+}
+
+#[constany::const_fn]
+fn nan_payload() -> [f32; 2] {
+    [f32::from_bits(0x7fc0_1234), -f32::from_bits(0x7fc0_0001)]
+}
+
+#[constany::const_fn]
+fn widened() -> u64 {
+    let small: u32 = u32::MAX;
+    small.into()
+}
+
+mod units {
+    use super::constany;
+
+    #[constany::const_fn]
+    fn kilo() -> u32 {
+        (0..3).map(|_| 10).product()
+    }
+
+    pub fn kilometres(metres: u32) -> u32 {
+        metres / kilo()
+    }
+
+    #[constany::const_fn(debug_pub)]
+    pub(super) fn origin() -> super::colors::Color {
+        super::colors::Color::Red
+    }
+
+    pub fn yards(inches: u32) -> u32 {
+        inches / imperial::yard()
+    }
+
+    pub(super) mod imperial {
+        use super::constany;
+
+        #[constany::const_fn]
+        pub(super) fn yard() -> u32 {
+            [3, 12].iter().product()
+        }
+    }
+}
+
+constany::assert_baked!(sum);
+constany::assert_baked!(table(&CONFIG, 2));
+constany::assert_baked!(raw);
+constany::assert_baked!(banner);
+constany::assert_baked!(powers);
+constany::assert_baked!(seed);
+constany::assert_baked!(noise);
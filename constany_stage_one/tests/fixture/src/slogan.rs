@@ -0,0 +1 @@
+format!("{} {}", "built", "once")
@@ -0,0 +1,23 @@
+#[cfg(any(
+    not(any(feature = "stage_one", feature = "stage_two")),
+    all(feature = "stage_two", feature = "stage_one")
+))]
+use constany_blank as constany;
+#[cfg(all(feature = "stage_one", not(feature = "stage_two")))]
+use constany_stage_one as constany;
+#[cfg(all(feature = "stage_two", not(feature = "stage_one")))]
+use constany_stage_two as constany;
+
+#[constany::main_fn(block)]
+fn main() {
+    println!("{}", block().iter().map(|i| u32::from(*i)).sum::<u32>());
+}
+
+#[constany::const_fn]
+fn block() -> [u8; 65536] {
+    let mut block = [0; 65536];
+    for (i, j) in block.iter_mut().enumerate() {
+        *j = (i ^ (i >> 8)) as u8;
+    }
+    block
+}
@@ -0,0 +1,804 @@
+//! Build `tests/fixture` with stage one, run it, and check the binary built by stage two.
+//!
+//! The tests share one fixture, built once with stage one, and take turns with it: a test changing it marks it dirty, and the next test restores it first.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+/// The dependencies of a crate using constany, on the crates of this workspace.
+fn dependencies() -> String {
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    ["constany_stage_one", "constany_stage_two", "constany_blank"]
+        .iter()
+        .map(|name| {
+            format!(
+                "{} = {{path = {:?}}}\n",
+                name,
+                workspace.join(name).to_string_lossy()
+            )
+        })
+        .collect()
+}
+
+/// Write the files of the fixture crate, depending on the crates of this workspace.
+fn write_fixture(dir: &Path) {
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::create_dir_all(dir.join("golden")).unwrap();
+    let manifest = format!(
+        "[package]\nname = \"constany_fixture\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n[workspace]\n\n[features]\nstage_one = []\nstage_two = []\nmetric = []\n\n[dependencies]\nserde_json = \"1\"\n{}",
+        dependencies()
+    );
+    std::fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+    for file in [
+        "src/main.rs",
+        "src/step.rs",
+        "src/slogan.rs",
+        "golden/wide.txt",
+    ] {
+        std::fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixture")
+                .join(file),
+            dir.join(file),
+        )
+        .unwrap();
+    }
+    // Stick to the versions this workspace is tested with.
+    std::fs::copy(workspace.join("Cargo.lock"), dir.join("Cargo.lock")).unwrap();
+}
+
+/// The fixture crate, after its first run of stage one.
+struct Fixture {
+    dir: PathBuf,
+    /// `src/main.rs`, as copied from `tests/fixture`.
+    source: String,
+    /// What the first run of stage one printed.
+    stderr: String,
+    /// Whether a test changed the sources or the resources, which the next test then restores.
+    dirty: bool,
+}
+
+impl Fixture {
+    /// Mark the fixture as changed, before changing it, so that it is restored even if the test fails.
+    fn change(&mut self) -> &Path {
+        self.dirty = true;
+        &self.dir
+    }
+    /// The record of a resource, after its header.
+    fn record(&self, name: &str) -> String {
+        let resource = std::fs::read(self.dir.join(format!("target/{}.res", name))).unwrap();
+        String::from_utf8(resource[17..].to_vec()).unwrap()
+    }
+}
+
+/// Remove the resources of a previous run in `dir`, which `incremental` would keep as long as their header is intact, even when a test broke what follows it.
+fn remove_resources(dir: &Path) {
+    if let Ok(entries) = std::fs::read_dir(dir.join("target")) {
+        for i in entries {
+            let path = i.unwrap().path();
+            if path.extension().is_some_and(|i| i == "res" || i == "rs") {
+                std::fs::remove_file(path).unwrap();
+            }
+        }
+    }
+}
+
+/// Lock the fixture, building it with stage one the first time, and restoring it when a previous test changed it.
+fn fixture() -> MutexGuard<'static, Fixture> {
+    static FIXTURE: OnceLock<Mutex<Fixture>> = OnceLock::new();
+    let mut fixture = FIXTURE
+        .get_or_init(|| Mutex::new(first_run()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if fixture.dirty {
+        write_fixture(&fixture.dir);
+        stage_one_afresh(&fixture.dir);
+        fixture.dirty = false;
+    }
+    fixture
+}
+
+/// Write the fixture and run stage one on it for the first time.
+fn first_run() -> Fixture {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("constany_fixture");
+    write_fixture(&dir);
+    let stderr = stage_one_afresh(&dir);
+    let source = std::fs::read_to_string(dir.join("src/main.rs")).unwrap();
+    Fixture {
+        dir,
+        source,
+        stderr,
+        dirty: false,
+    }
+}
+
+/// Run stage one on the fixture without the resources of a previous run, which `incremental` would keep, and return what it printed.
+fn stage_one_afresh(dir: &Path) -> String {
+    remove_resources(dir);
+    let output = cargo(
+        dir,
+        "run",
+        "stage_one",
+        &[
+            ("CONSTANY_TIMING", "1"),
+            ("CONSTANY_INCLUDE_ABOVE", "10000"),
+        ],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert!(output.status.success(), "stage_one failed:\n{}", stderr);
+    stderr
+}
+
+/// Write a workspace whose binary evaluates a constant function of its library, from `tests/workspace`.
+fn workspace_fixture() -> PathBuf {
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("constany_workspace");
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    std::fs::create_dir_all(dir.join("shapes/src")).unwrap();
+    let binary = format!(
+        "[package]\nname = \"constany_app\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n[workspace]\nmembers = [\"shapes\"]\n\n[features]\nstage_one = [\"shapes/stage_one\"]\nstage_two = [\"shapes/stage_two\"]\n\n[dependencies]\nshapes = {{path = \"shapes\"}}\n{}",
+        dependencies()
+    );
+    let library = format!(
+        "[package]\nname = \"shapes\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n[features]\nstage_one = []\nstage_two = []\n\n[dependencies]\n{}",
+        dependencies()
+    );
+    std::fs::write(dir.join("Cargo.toml"), binary).unwrap();
+    std::fs::write(dir.join("shapes/Cargo.toml"), library).unwrap();
+    for file in ["src/main.rs", "shapes/src/lib.rs"] {
+        std::fs::copy(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/workspace")
+                .join(file),
+            dir.join(file),
+        )
+        .unwrap();
+    }
+    let _ = std::fs::remove_file(dir.join("target/corners.res"));
+    std::fs::copy(workspace.join("Cargo.lock"), dir.join("Cargo.lock")).unwrap();
+    dir
+}
+
+/// Write a crate using constany from `tests/<name>`, such as `tests/large` hard-coding a 64 KB array, without the resources of a previous run.
+fn crate_fixture(name: &str) -> PathBuf {
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let package = format!("constany_{}", name);
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(&package);
+    std::fs::create_dir_all(dir.join("src")).unwrap();
+    let manifest = format!(
+        "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n[workspace]\n\n[features]\nstage_one = []\nstage_two = []\n\n[dependencies]\n{}",
+        package,
+        dependencies()
+    );
+    std::fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+    std::fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join(name)
+            .join("src/main.rs"),
+        dir.join("src/main.rs"),
+    )
+    .unwrap();
+    remove_resources(&dir);
+    std::fs::copy(workspace.join("Cargo.lock"), dir.join("Cargo.lock")).unwrap();
+    dir
+}
+
+/// Run the fixture with a stage enabled.
+fn cargo_run(dir: &Path, feature: &str) -> std::process::Output {
+    cargo(dir, "run", feature, &[])
+}
+
+/// Run a cargo command on the fixture with a stage enabled.
+fn cargo(dir: &Path, command: &str, feature: &str, envs: &[(&str, &str)]) -> std::process::Output {
+    Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args([command, "--quiet", "--features", feature])
+        .envs(envs.iter().copied())
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+/// Run the fixture with a stage enabled, and return what it printed.
+fn run(dir: &Path, feature: &str) -> String {
+    let output = cargo_run(dir, feature);
+    assert!(
+        output.status.success(),
+        "{} failed:\n{}",
+        feature,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn stage_one_records() {
+    let fixture = fixture();
+    let (dir, stderr) = (&fixture.dir, &fixture.stderr);
+    assert!(stderr.contains("constany: sum took ") && stderr.contains("constany: checksum took "));
+    assert!(stderr.contains("constany: warning: hard-coding the absolute path \"/\""));
+    assert!(stderr.contains("constany: warning: `tripled` looks simple enough to be a `const fn`"));
+    assert!(!stderr.contains("`sum` looks simple enough"));
+    assert!(stderr.contains("constany: warning: `pair` is hard-coded with `memop`, which copies the bytes of `Pair` as they are"));
+    let method = |name: &str| std::fs::read(dir.join(format!("target/{}.res", name))).unwrap()[0];
+    assert_eq!(method("sum"), 0);
+    assert_eq!(method("point"), 0);
+    assert_eq!(method("pair"), 1);
+    assert_eq!(method("cname"), 2);
+    assert_eq!(method("noise"), 2);
+    assert!(!dir.join("target/step.res").exists());
+    assert!(fixture
+        .record("frame")
+        .contains("\n    origin: (\n        -3,\n"));
+    assert!(fixture
+        .record("compact_frame")
+        .starts_with("Frame { origin: (-3, 4),"));
+    // A string recorded by a function evaluated before only names it.
+    assert_eq!(fixture.record("motd"), "\"hello again\"");
+    assert_eq!(fixture.record("welcome"), "=motd\n\"hello again\"");
+    let layout = std::fs::read(dir.join("target/layout.res")).unwrap();
+    assert_eq!(layout[17..].iter().filter(|i| **i == b'\n').count(), 3);
+    assert_eq!(
+        std::fs::read_to_string(dir.join("target/squares.rs")).unwrap(),
+        "[(1, \"one\"), (4, \"four\"), (9, \"nine\")]"
+    );
+}
+
+#[test]
+fn include_above() {
+    let fixture = fixture();
+    // A record above `CONSTANY_INCLUDE_ABOVE` is written to a file for `include!`.
+    assert!(fixture
+        .stderr
+        .contains("constany: note: `powers` hard-codes "));
+    assert!(fixture.record("powers").is_empty() && fixture.dir.join("target/powers.rs").exists());
+    assert!(!fixture.record("sum").is_empty() && !fixture.dir.join("target/sum.rs").exists());
+}
+
+#[test]
+fn incremental() {
+    let fixture = fixture();
+    // Running stage one again leaves the resources of unchanged functions alone.
+    let modified = || {
+        std::fs::metadata(fixture.dir.join("target/sum.res"))
+            .unwrap()
+            .modified()
+            .unwrap()
+    };
+    let before = modified();
+    run(&fixture.dir, "stage_one");
+    assert_eq!(modified(), before);
+}
+
+#[test]
+fn incremental_arguments() {
+    let mut fixture = fixture();
+    let source = fixture.source.clone();
+    let dir = fixture.change();
+    // A function called with arguments is evaluated again, since its source does not tell when they change.
+    let changed = source.replace("table(&CONFIG, 2), step", "table(&CONFIG, 3), step");
+    std::fs::write(dir.join("src/main.rs"), changed).unwrap();
+    run(dir, "stage_one");
+    assert_eq!(fixture.record("table"), "[0, 24, 48, 72]");
+}
+
+#[test]
+fn resource_dir() {
+    let mut fixture = fixture();
+    let source = fixture.source.clone();
+    let dir = fixture.change();
+    // Stage one creates the resource directory when it does not exist. The macros are expanded again to pick up the directory.
+    let resources = dir.join("target/constany_resources");
+    let _ = std::fs::remove_dir_all(&resources);
+    std::fs::write(dir.join("src/main.rs"), source + "\n").unwrap();
+    let output = cargo(
+        dir,
+        "run",
+        "stage_one",
+        &[("CONSTANY_RESOURCE_DIR", "target/constany_resources")],
+    );
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(resources.join("sum.res").exists());
+}
+
+#[test]
+fn stage_two_values() {
+    let fixture = fixture();
+    assert_eq!(
+        run(&fixture.dir, "stage_two"),
+        concat!(
+            "21 168\n",
+            "(['a', '\\'', '\\u{301}', '🦀'], [true, false])\n",
+            "(NaN, [inf, -inf, 0.5])\n",
+            "\"a\\\"ba\\\"b\" [127.0.0.1] Some(Err(3))\n",
+            "[1, 2, 3, 4] \"lib7\"\n",
+            "Point { x: -1, y: (0.5, true) } Pair { a: 7, b: [[1, 2], [3, 4]] }\n",
+            "Frame { origin: (-3, 4), corners: [[0, 1], [2, 3]], label: \"a \\\"frame\\\",\\n\" } true\n",
+            "40 #1 #40\n",
+            "55\n",
+            "[(\"name\", \"constany_fixture\"), (\"greeting\", \"say \\\"hi\\\",\\n\"), (\"path\", \"C:\\\\temp\")] [(\"name\", \"constany_fixture\"), (\"greeting\", \"say \\\"hi\\\",\\n\"), (\"path\", \"C:\\\\temp\")] []\n",
+            "4294967295\n",
+            "[1, 1501199875790165, 9218868437227405312, 4599075939470750516]\n",
+            "8 [4, 6, 10, 14, 22, 26, 34, 38]\n",
+            "Ok(1600000000.000000005s)\n",
+            "Red Green Blue\n",
+            "[(1, \"one\"), (4, \"four\"), (9, \"nine\")]\n",
+            "[[1, 2, 3], [4, 5, 6]] [[[0.5, NaN], [-1.0, 2.0]], [[3.0, inf], [4.0, 5.5]]]\n",
+            "[\"fn\", \"\\\"quoted\\\"\", \"tab\\t\", \"é\"] [\"fn\", \"\\\"quoted\\\"\", \"tab\\t\", \"é\"] []\n",
+            "Some(42) Some(42) hi hi\n",
+            "285\n",
+            "(340282366920938463463374607431768211455, [-170141183460469231731687303715884105728, 170141183460469231731687303715884105727], -128) -170141183460469231731687303715884105728\n",
+            "Some(\"assets/icon \\\"1\\\".png\") \"/\"\n",
+            "[0, 16, 32, 48] 42\n",
+            "1024 16084289632517659307 true\n",
+            "[2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47] true\n",
+            "\"roses\\nviolets\\n\" 640 480\n",
+            "[\"\", \"tab\\t\\\"é\\\"\"]\n",
+            "100 Some(\"°C\")\n",
+            "6..11 'b'..='e' true\n",
+            "[1, 2, 3, 4] \"con\\\"stany\" \"héhé\" Some(\"me\")\n",
+            "None\n",
+            "[(\"alice\", 3), (\"\\\"bob\\\"\", 5)] [(\"Accept\", None), (\"X-X-\", Some(1))]\n",
+            "Blob([0, 51, 102, 153, 204, 255, 62, 63]) 42\n",
+            "42 constany_fixture v0.1.0 constany_fixture v0.1.0\n",
+            "None\n",
+            "[0, 2, 4, 6, 8] {\"any\": 3, \"const\": 5, \"fn\": 2} {'a', 'o'} \"ca\"\n",
+            "243 160558814 255 2432902008176640000\n",
+            "[0, 3, 6, 9] 18 4 ([0, 3, 6, 9], 18, 4)\n",
+            "5 10 mi\n",
+            "256 [255, 254, 253, 252]\n",
+            "10 10 -6\n",
+            "true Some(\"constany_fixture\")\n",
+            "61 6\n",
+            "24\n",
+            "hello again hello again\n",
+            "[7fc01234, ffc00001]\n",
+            "3\n",
+            "Green Red\n",
+            "built once\n",
+            "16 49\n",
+            "[63, 61, 62, 58, 59, 64, 65, 60]\n",
+        )
+    );
+}
+
+/// Expand the macros of the fixture in `dir` for stage two, with the build shared with the fixture.
+fn expand(dir: &Path, fixture: &Path, envs: &[(&str, &str)]) -> String {
+    // `-Zunpretty` is only available on nightly, unless the compiler is told otherwise.
+    let output = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args([
+            "rustc",
+            "--quiet",
+            "--features",
+            "stage_two",
+            "--bin",
+            "constany_fixture",
+            "--",
+            "-Zunpretty=expanded",
+        ])
+        .env("RUSTC_BOOTSTRAP", "1")
+        .env("CARGO_TARGET_DIR", fixture.join("target"))
+        .envs(envs.iter().copied())
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn reproducible() {
+    let fixture = fixture();
+    // Two checkouts, each running both stages with `CONSTANY_REPRODUCIBLE=1`, generate the same code, although `catalog` is a `HashMap` and `built` reads the clock.
+    let reproducible = [("CONSTANY_REPRODUCIBLE", "1")];
+    let expanded = ["constany_checkout_a", "constany_checkout_b"].map(|i| {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(i);
+        let _ = std::fs::remove_dir_all(&dir);
+        write_fixture(&dir);
+        let output = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+            .args(["run", "--quiet", "--features", "stage_one"])
+            .env("CARGO_TARGET_DIR", fixture.dir.join("target"))
+            .envs(reproducible)
+            .current_dir(&dir)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        expand(&dir, &fixture.dir, &reproducible)
+    });
+    assert!(expanded[0].contains("CONST_VALUE_OF_FN_catalog"));
+    assert_eq!(expanded[0], expanded[1]);
+}
+
+#[test]
+fn baked_tests() {
+    let fixture = fixture();
+    let dir = &fixture.dir;
+    // Tests compare the hard-coded values with the original functions.
+    let output = cargo(dir, "test", "stage_two", &[]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    let output = cargo(dir, "test", "stage_two", &[("FIXTURE_SEED", "1")]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("_seed_baked_test")
+            && stdout.contains(
+                "The hard-coded value of `seed` differs from the value of the original function"
+            )
+            && stdout.contains("6 passed; 1 failed"),
+        "{}",
+        stdout
+    );
+}
+
+#[test]
+fn out_of_date() {
+    let mut fixture = fixture();
+    let source = fixture.source.clone();
+    let dir = fixture.change();
+    // Checking with stage two notices every function changed since stage one, and tells when its return type changed.
+    let changed = source
+        .replace("(1..7).sum()", "(1..8).sum()")
+        .replace(
+            "fn limit() -> Option<u32> {",
+            "fn limit() -> Option<u32> {\n    let _ = 1;",
+        )
+        .replace("fn answer() -> u32", "fn answer() -> u64")
+        .replace("product::<u32>()", "product::<u64>()");
+    std::fs::write(dir.join("src/main.rs"), changed).unwrap();
+    let output = cargo(dir, "check", "stage_two", &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("The resource of `sum` is out of date")
+            && stderr.contains("The resource of `limit` is out of date")
+            && stderr.contains("The return type of `answer` changed since stage one"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn golden() {
+    let mut fixture = fixture();
+    let dir = fixture.change();
+    // A value that differs from its golden file is reported with where it differs.
+    let golden = dir.join("golden/wide.txt");
+    let expected = std::fs::read_to_string(&golden).unwrap();
+    std::fs::write(&golden, expected.replace("-128", "-127")).unwrap();
+    let output = cargo(dir, "check", "stage_two", &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("The value of `wide` differs from `golden/wide.txt` at line 1, column 131: expected \"7)\", found \"8)\""),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn unsupported() {
+    let mut fixture = fixture();
+    let source = fixture.source.clone();
+    let dir = fixture.change();
+    // Unsupported functions fail with a message saying why: the macro cannot resolve an alias but points to `as`, borrowed values cannot be hard-coded, `split` needs a name per element, and `wrapper` needs an identifier.
+    let unsupported = source.replace(
+        "#[constany::const_fn(as = \"u32\")]\nfn boiling",
+        "#[constany::const_fn]\nfn boiling",
+    )
+        + "\n#[constany::const_fn]\nfn first(text: &str) -> Option<&str> {\n    text.get(..1)\n}\n"
+        + "\n#[constany::const_fn(split(LOW))]\nfn bounds() -> (u8, u8) {\n    (0, 9)\n}\n"
+        + "\n#[constany::const_fn(wrapper = \"evaluate odd\")]\nfn odd() -> u8 {\n    1\n}\n";
+    std::fs::write(dir.join("src/main.rs"), unsupported).unwrap();
+    let output = cargo(dir, "check", "stage_two", &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("If `Celsius` is a type alias, give the real type with `as`")
+            && stderr.contains(
+                "`Option < & str >` borrows from something that only lives while the function runs"
+            )
+            && stderr.contains("`split` needs a tuple with as many elements as names: 1 here")
+            && stderr.contains("`wrapper` needs the name of a function"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn stage_one_checks() {
+    let mut fixture = fixture();
+    let source = fixture.source.clone();
+    let dir = fixture.change();
+    // Stage one checks that a function returns the type given with `as`, and that `main_fn` lists something to evaluate.
+    let list = source.find("#[constany::main_fn(").unwrap();
+    let list = list..list + source[list..].find(")]\nfn main").unwrap() + 2;
+    let mut mismatched = source.replace("as = \"u32\"", "as = \"i64\"");
+    mismatched.replace_range(list, "#[constany::main_fn()]");
+    std::fs::write(dir.join("src/main.rs"), mismatched).unwrap();
+    let output = cargo(dir, "check", "stage_one", &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("mismatched types")
+            && stderr.contains("as = \"i64\"")
+            && stderr.contains("`main_fn` lists no constant function"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn listed_twice() {
+    let mut fixture = fixture();
+    let source = fixture.source.clone();
+    let dir = fixture.change();
+    // Both entries would write the same resource.
+    let twice = source.replace(
+        "table(&CONFIG, 2), step",
+        "table(&CONFIG, 2), table(&CONFIG, 3), step",
+    );
+    std::fs::write(dir.join("src/main.rs"), twice).unwrap();
+    let output = cargo(dir, "check", "stage_one", &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("`table` is listed more than once in `main_fn`"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn depends_env() {
+    let fixture = fixture();
+    // A function is out of date when a variable in `depends_env` changed since stage one.
+    let output = cargo(
+        &fixture.dir,
+        "check",
+        "stage_two",
+        &[("FIXTURE_PROFILE", "release")],
+    );
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("The resource of `profile` is out of date"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn depends_feature() {
+    let mut fixture = fixture();
+    let dir = fixture.change();
+    // A function is out of date when it was recorded with other features in `depends_feature`.
+    let output = cargo(dir, "check", "stage_two,metric", &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "The resource of `distance_unit` is out of date, or was recorded with other features"
+        ),
+        "{}",
+        stderr
+    );
+    run(dir, "stage_one,metric");
+    assert!(run(dir, "stage_two,metric").contains("5 10 km\n"));
+}
+
+#[test]
+fn missing_resource() {
+    let mut fixture = fixture();
+    let dir = fixture.change();
+    // Without the resource, stage two names the missing file.
+    std::fs::remove_file(dir.join("target/sum.res")).unwrap();
+    let output = cargo_run(dir, "stage_two");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let path = dir.join("target/sum.res");
+    assert!(
+        stderr.contains(&format!("Unable to read `{}` (NotFound", path.display())),
+        "{}",
+        stderr
+    );
+    // In strict mode, the message also says where the resource was expected and how to regenerate it.
+    let output = cargo(dir, "check", "stage_two", &[("CONSTANY_STRICT", "1")]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "`sum` has no valid resource, and `CONSTANY_STRICT=1` forbids building it without one"
+        ) && stderr.contains(&format!("expected resource: {}", path.display()))
+            && stderr.contains("to regenerate it: run stage one"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn broken_resource() {
+    let mut fixture = fixture();
+    let dir = fixture.change();
+    // A resource edited by hand is reported at the function or expression, not as a panic of the macro.
+    for i in std::fs::read_dir(dir.join("target")).unwrap() {
+        let path = i.unwrap().path();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        if name == "sum.res" || (name.starts_with("expr_") && name.ends_with(".res")) {
+            let mut resource = std::fs::read(&path).unwrap();
+            resource.truncate(17);
+            resource.extend_from_slice(b"u8\n\"unterminated");
+            std::fs::write(&path, resource).unwrap();
+        }
+    }
+    let output = cargo(dir, "check", "stage_two", &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.matches("Broken resource file").count() == 3 && !stderr.contains("panicked"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn panicking_function() {
+    let mut fixture = fixture();
+    let dir = fixture.change();
+    // A panicking function is reported by stage one, and leaves an empty resource for stage two.
+    std::fs::remove_file(dir.join("target/fragile.res")).unwrap();
+    let output = cargo(dir, "run", "stage_one", &[("FIXTURE_PANIC", "1")]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("asked to panic")
+            && stderr.contains("constany: fragile panicked, so it was not evaluated"),
+        "{}",
+        stderr
+    );
+    assert!(std::fs::read(dir.join("target/fragile.res"))
+        .unwrap()
+        .is_empty());
+    let output = cargo(dir, "check", "stage_two", &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("is empty, because the function panicked"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn panicking_pure_function() {
+    let mut fixture = fixture();
+    let dir = fixture.change();
+    // The environment taken away from a `pure` function is given back even when it panics, for the functions evaluated after it, in the order of their names.
+    std::fs::remove_file(dir.join("target/hermetic.res")).unwrap();
+    std::fs::remove_file(dir.join("target/package.res")).unwrap();
+    std::fs::write(dir.join("target/panic_hermetic"), "").unwrap();
+    let output = cargo(dir, "run", "stage_one", &[]);
+    std::fs::remove_file(dir.join("target/panic_hermetic")).unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("constany: hermetic panicked, so it was not evaluated"),
+        "{}",
+        stderr
+    );
+    assert_eq!(fixture.record("package"), "Some(\"constany_fixture\")");
+}
+
+#[test]
+fn inspect_hook() {
+    let mut fixture = fixture();
+    let dir = fixture.change();
+    // A value rejected by the `inspect` hook is not written, and stage one fails.
+    std::fs::remove_file(dir.join("target/checksum.res")).unwrap();
+    let output = cargo(dir, "run", "stage_one", &[("FIXTURE_REJECT", "checksum")]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("constany: check_record rejected the value of checksum: asked to reject"),
+        "{}",
+        stderr
+    );
+    assert!(!dir.join("target/checksum.res").exists());
+}
+
+#[test]
+fn library_and_binary() {
+    let dir = workspace_fixture();
+    // Both crates are compiled from the root of the workspace, so they share its `target` directory.
+    run(&dir, "stage_one");
+    assert!(dir.join("target/corners.res").exists());
+    assert_eq!(run(&dir, "stage_two"), "[90, 180, 270, 360]\n");
+}
+
+#[test]
+fn large_array() {
+    let dir = crate_fixture("large");
+    run(&dir, "stage_one");
+    // The 300 KB record goes through the macro of stage two.
+    assert_eq!(run(&dir, "stage_two"), "8355840\n");
+}
+
+/// Measure building a 1 MB `Vec<u8>` hard-coded by stage two and building the same `vec![..]` written by hand, in release mode, and print both times. It can take a quarter of an hour, so it only runs with `cargo test -- --ignored --nocapture`.
+#[test]
+#[ignore]
+fn byte_vec_benchmark() {
+    let dir = crate_fixture("blob");
+    run(&dir, "stage_one");
+    let release = || {
+        Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+            .args(["build", "--quiet", "--release", "--features", "stage_two"])
+            .current_dir(&dir)
+            .status()
+            .unwrap()
+    };
+    assert!(release().success());
+    // Only the crate itself is built again.
+    std::fs::write(
+        dir.join("src/main.rs"),
+        std::fs::read_to_string(dir.join("src/main.rs")).unwrap() + "\n",
+    )
+    .unwrap();
+    let started = std::time::Instant::now();
+    assert!(release().success());
+    let baked = started.elapsed();
+    eprintln!("byte string: {:?}", baked);
+
+    let literal = Path::new(env!("CARGO_TARGET_TMPDIR")).join("constany_vec_literal");
+    std::fs::create_dir_all(literal.join("src")).unwrap();
+    std::fs::write(
+        literal.join("Cargo.toml"),
+        "[package]\nname = \"constany_vec_literal\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n[workspace]\n",
+    )
+    .unwrap();
+    let bytes: Vec<String> = (0..1u32 << 20)
+        .map(|i| ((i ^ (i >> 8)) as u8).to_string())
+        .collect();
+    std::fs::write(
+        literal.join("src/main.rs"),
+        format!(
+            "fn blob() -> Vec<u8> {{\n    vec![{}]\n}}\n\nfn main() {{\n    println!(\"{{}}\", blob().len());\n}}\n",
+            bytes.join(", ")
+        ),
+    )
+    .unwrap();
+    let started = std::time::Instant::now();
+    let mut build = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args(["build", "--quiet", "--release"])
+        .current_dir(&literal)
+        .spawn()
+        .unwrap();
+    // It may not finish in any reasonable time, so it is stopped after 15 minutes.
+    let limit = std::time::Duration::from_secs(15 * 60);
+    while build.try_wait().unwrap().is_none() && started.elapsed() < limit {
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+    let finished = build.try_wait().unwrap().is_some();
+    let _ = build.kill();
+    eprintln!(
+        "vec![..]: {:?}{}",
+        started.elapsed(),
+        if finished { "" } else { ", stopped" }
+    );
+}
@@ -0,0 +1,15 @@
+#[cfg(any(
+    not(any(feature = "stage_one", feature = "stage_two")),
+    all(feature = "stage_two", feature = "stage_one")
+))]
+use constany_blank as constany;
+#[cfg(all(feature = "stage_one", not(feature = "stage_two")))]
+use constany_stage_one as constany;
+#[cfg(all(feature = "stage_two", not(feature = "stage_one")))]
+use constany_stage_two as constany;
+
+/// Evaluated by the stage one of the binary, which lists it in `main_fn`.
+#[constany::const_fn]
+pub fn corners() -> Vec<u32> {
+    (1..=4).map(|i| i * 90).collect()
+}
@@ -0,0 +1,14 @@
+#[cfg(any(
+    not(any(feature = "stage_one", feature = "stage_two")),
+    all(feature = "stage_two", feature = "stage_one")
+))]
+use constany_blank as constany;
+#[cfg(all(feature = "stage_one", not(feature = "stage_two")))]
+use constany_stage_one as constany;
+#[cfg(all(feature = "stage_two", not(feature = "stage_one")))]
+use constany_stage_two as constany;
+
+#[constany::main_fn(shapes::corners)]
+fn main() {
+    println!("{:?}", shapes::corners());
+}
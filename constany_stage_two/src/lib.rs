@@ -4,130 +4,699 @@
 extern crate proc_macro;
 
 use crate::proc_macro::TokenStream;
+use constany_common::{rebuild, Emit, Method, Mode, Options, SlicePointer};
 use quote::quote;
-use syn;
+
+/// Decode the hex payload written by stage one for `memop` functions.
+fn decode_hex(input: &str) -> Option<Vec<u8>> {
+    input
+        .as_bytes()
+        .chunks(2)
+        .map(|i| match i {
+            [high, low] => u8::from_str_radix(std::str::from_utf8(&[*high, *low]).ok()?, 16).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Encode bytes as standard base64 with padding, for `base64` functions.
+fn encode_base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(input.len().div_ceil(3) * 4);
+    for i in input.chunks(3) {
+        let block = i
+            .iter()
+            .enumerate()
+            .fold(0u32, |block, (j, k)| block | (*k as u32) << (16 - 8 * j));
+        for j in 0..4 {
+            if j <= i.len() {
+                encoded.push(ALPHABET[(block >> (18 - 6 * j) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
 
 #[proc_macro_attribute]
 pub fn const_fn(attr: TokenStream, bare_item: TokenStream) -> TokenStream {
+    let options = match Options::from_args(&syn::parse_macro_input!(attr as syn::AttributeArgs)) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
+    };
     let item: syn::ItemFn = syn::parse(bare_item.clone()).unwrap();
+    if let Err(e) = constany_common::check_single_stage(&item, "const_fn") {
+        return e.to_compile_error().into();
+    }
+    let item = match constany_common::rewrite(item, &options) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
+    };
     let name = &item.sig.ident;
-    let visibility = &item.vis;
-    let data = std::fs::read(&format!("target/{}.res", item.sig.ident.to_string())).expect("Unable to load function content resource. Please make sure you have executed --stage-one before compiling the final product.");
-    use std::convert::TryInto;
-    if u64::from_be_bytes(
-        data[1..9]
-            .try_into()
-            .expect("Broken resource file. Please execute stage one again."),
-    ) != seahash::hash(bare_item.to_string().as_bytes())
-    {
-        panic!("Incorrect function hash. Please make sure you have executed --stage-one before compiling the final product.")
+    // The generated items are public in a private module named after the function, which only re-exports them with the visibility of the function.
+    let visibility = quote! { pub };
+    let output_type = match constany_common::output_type(&item.sig) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
     };
+    let output_type = constany_common::value_type(output_type, &options);
+    if let Err(e) = constany_common::check_options(output_type, &options) {
+        return e.to_compile_error().into();
+    }
     let return_type = &item.sig.output;
-    let real_data = String::from_utf8(data[9..].to_vec()).unwrap();
-    let const_value = if attr.to_string().contains("force_const") {
-        true
+    // The value was recorded for the arguments listed in `main_fn`, so the parameters are ignored.
+    let types = constany_common::parameter_types(&item.sig);
+    let parameters = quote! { #(_: #types),* };
+    // An argument taken by value may have to be dropped, which a `const fn` cannot do, and the expression in a `manual` file is only known to be const for types that stage two would rebuild in a const.
+    let constness = if types.iter().all(|i| constany_common::drops_nothing(i))
+        && (options.manual.is_none() || constany_common::rebuild::is_const(output_type))
+    {
+        quote! { const }
     } else {
-        false
+        quote! {}
     };
-    let constructed = match data[0] {
-        0 => {
-            // A super hacky method to generate useable result.
-            if const_value {
-                let const_name = quote::format_ident!("CONST_VALUE_OF_FN_{}", name);
-                let output_type_2 = match return_type {
-                    syn::ReturnType::Default => unimplemented!(),
-                    syn::ReturnType::Type(_, j) => j,
+    // Callers of an `unsafe fn` already use an `unsafe` block, and an `extern` function may be exposed through FFI, so the generated function keeps both qualifiers.
+    // It is `const` whenever the value allows it, whether the original function was or not.
+    let unsafety = &item.sig.unsafety;
+    let abi = &item.sig.abi;
+    let qualifiers = quote! { #unsafety #abi };
+    // Attributes such as `#[no_mangle]`, `#[inline]` or doc comments apply to the generated function, which replaces the original one.
+    let attrs = &item.attrs;
+    let const_value = options.force_const;
+    let const_name = quote::format_ident!("CONST_VALUE_OF_FN_{}", name);
+    // The original function is kept for tests, so that `assert_baked!` can compare it with the hard-coded value.
+    let original = {
+        let mut original = item.clone();
+        original.attrs.clear();
+        original.vis = syn::Visibility::Inherited;
+        original.sig.ident = quote::format_ident!("_{}_original_fn", name.to_string());
+        quote! {
+            #[cfg(test)]
+            #[allow(dead_code)]
+            #original
+        }
+    };
+    let item_name = quote::format_ident!(
+        "{}",
+        name.to_string().trim_start_matches("r#").to_uppercase()
+    );
+    let module = quote::format_ident!("__constany_{}", name.to_string().trim_start_matches("r#"));
+    let split = &options.split;
+    let len_name = quote::format_ident!("{}_LEN", item_name);
+    let mut exported = vec![quote! { #name }];
+    match options.emit {
+        Emit::Fn => exported.extend(split.iter().map(|i| quote! { #i })),
+        Emit::ConstItem | Emit::StaticItem => exported.push(quote! { #item_name }),
+    }
+    if options.with_len {
+        exported.push(quote! { #len_name });
+    }
+    let exported = quote! { {#(#exported),*} };
+    let exported_visibility = &item.vis;
+    let export = |constructed: proc_macro2::TokenStream| {
+        let constructed = constany_common::reparent(constructed);
+        quote! {
+            #[doc(hidden)]
+            mod #module {
+                #[allow(unused_imports)]
+                use super::*;
+                #constructed
+            }
+            #exported_visibility use #module::#exported;
+        }
+    };
+    // Items for a value that can be built in a const.
+    let constant = |value: proc_macro2::TokenStream| {
+        // The tuple is built once, and each constant takes one of its elements.
+        if let syn::Type::Tuple(i) = output_type.clone() {
+            if !split.is_empty() {
+                let types = i.elems.iter();
+                let indices = (0..split.len()).map(syn::Index::from);
+                return quote! {
+                    #[allow(non_upper_case_globals, dead_code)]
+                    const #const_name: #output_type = #value;
+                    #(#visibility const #split: #types = #const_name.#indices;)*
+                    #(#attrs)*
+                    #visibility #constness #qualifiers fn #name(#parameters) #return_type {
+                        #const_name
+                    }
                 };
-                let generated = quote! {
-                    const #const_name: #output_type_2 = DONOTTOUCHME;
+            }
+        }
+        match options.emit {
+            // A `static` has a single address, so the value is never copied.
+            Emit::Fn if options.static_ref => quote! {
+                #(#attrs)*
+                #visibility #qualifiers fn #name(#parameters) #return_type {
+                    static VALUE: #output_type = #value;
+                    &VALUE
+                }
+            },
+            Emit::Fn if const_value => quote! {
+                #[allow(non_upper_case_globals, dead_code)]
+                const #const_name: #output_type = #value;
+                #(#attrs)*
+                #visibility #constness #qualifiers fn #name(#parameters) #return_type {
+                    #const_name
                 }
-                .to_string();
-                let generated = generated.replace("DONOTTOUCHME", &real_data);
-                let generated: syn::Item = syn::parse_str(&generated).unwrap();
+            },
+            Emit::Fn => quote! {
+                #(#attrs)*
+                #visibility #constness #qualifiers fn #name(#parameters) #return_type {
+                    #value
+                }
+            },
+            Emit::ConstItem => quote! {
+                #visibility const #item_name: #output_type = #value;
+                #(#attrs)*
+                #visibility #constness #qualifiers fn #name(#parameters) #return_type {
+                    #item_name
+                }
+            },
+            Emit::StaticItem => quote! {
+                #visibility static #item_name: #output_type = #value;
+                #(#attrs)*
+                #visibility #qualifiers fn #name(#parameters) #return_type {
+                    #item_name
+                }
+            },
+        }
+    };
+    // The value is pasted from the file by the compiler, and there is no resource to check.
+    if let Some(path) = &options.manual {
+        let path = constany_common::include_arg(&constany_common::manifest_path(path));
+        let constructed = export(constant(quote! { include!(#path) }));
+        let constructed = quote! {
+            #constructed
+            #original
+        };
+        return constructed.into();
+    }
+    let resource_error = |problem: &str| {
+        syn::Error::new_spanned(
+            name,
+            constany_common::resource_error(&name.to_string(), problem),
+        )
+        .to_compile_error()
+        .into()
+    };
+    let broken = || resource_error("Broken resource file. Please execute stage one again.");
+    let data = match constany_common::read_resource(&name.to_string()) {
+        Ok(i) => i,
+        Err(e) => return resource_error(&e),
+    };
+    let (method, real_data) = match (
+        Method::from_byte(data[0]),
+        String::from_utf8(data[constany_common::HEADER_LEN..].to_vec()),
+    ) {
+        (Some(i), Ok(j)) => (i, j),
+        _ => return broken(),
+    };
+    let (shared, real_data) = match constany_common::split_shared(&real_data) {
+        (Some(i), j) => (Some(i.to_string()), j.to_string()),
+        (None, _) => (None, real_data),
+    };
+    let expected_method = match Method::of(output_type, options.mode) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    use std::convert::TryInto;
+    let recorded_type = u64::from_be_bytes(data[9..17].try_into().unwrap());
+    if recorded_type != constany_common::type_hash(output_type) {
+        return resource_error(&format!(
+            "The return type of `{}` changed since stage one. Please execute stage one again",
+            name
+        ));
+    }
+    if method != expected_method {
+        return resource_error(
+            "The resource was generated for a different return type. Please execute stage one again.",
+        );
+    }
+    let code_hash = match constany_common::source_hash(&bare_item.to_string(), &options) {
+        Ok(i) => i,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let recorded_hash = u64::from_be_bytes(data[1..9].try_into().unwrap());
+    // With `depends_feature`, the hash is only known once the crate is compiled, so the generated code compares it.
+    let feature_check = if options.depends_feature.is_empty() {
+        quote! {}
+    } else {
+        let features = constany_common::feature_hash(&options);
+        let message = constany_common::resource_error(
+            &name.to_string(),
+            &format!(
+                "The resource of `{}` is out of date, or was recorded with other features. Please execute stage one again",
+                name
+            ),
+        );
+        quote! {
+            const _: () = if #recorded_hash != #code_hash ^ #features {
+                panic!(#message)
+            };
+        }
+    };
+    if options.depends_feature.is_empty() && recorded_hash != code_hash {
+        return resource_error(&format!(
+            "The resource of `{}` is out of date. Please execute stage one again",
+            name
+        ));
+    };
+    // Stage one also writes a large record to a file for `include` by itself, and then leaves the resource empty, since `Debug` never prints nothing.
+    let included = options.include || (method == Method::Debug && real_data.is_empty());
+    if let Some(golden) = &options.golden {
+        let record = if included {
+            std::fs::read_to_string(constany_common::include_path(&name.to_string()))
+                .unwrap_or_default()
+        } else {
+            real_data.clone()
+        };
+        if let Err(e) = constany_common::check_golden(&name.to_string(), &record, golden) {
+            return syn::Error::new_spanned(name, e).to_compile_error().into();
+        }
+    }
+    if let Some(max_bytes) = options.max_bytes {
+        let size = match method {
+            Method::Debug if included => {
+                std::fs::metadata(constany_common::include_path(&name.to_string()))
+                    .map(|i| i.len() as usize)
+                    .unwrap_or(0)
+            }
+            Method::Debug | Method::Serde => real_data.len(),
+            Method::Memop | Method::Bytes | Method::Base64 => real_data.len() / 2,
+        };
+        if size > max_bytes {
+            return syn::Error::new_spanned(
+                name,
+                format!(
+                    "`{}` hard-codes {} bytes, which exceeds the budget of {} bytes",
+                    name, size, max_bytes
+                ),
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+    // The function recording the same string first is called instead, as long as its resource still holds that string: it may have been evaluated again since.
+    let shared = shared.filter(|i| {
+        syn::parse_str::<syn::Ident>(i).is_ok()
+            && constany_common::read_resource(i).is_ok_and(|j| {
+                j[0] == data[0]
+                    && j[9..constany_common::HEADER_LEN] == data[9..constany_common::HEADER_LEN]
+                    && j[constany_common::HEADER_LEN..] == *real_data.as_bytes()
+            })
+    });
+    let constructed = match method {
+        Method::Debug if shared.is_some() => {
+            let first = quote::format_ident!("{}", shared.unwrap());
+            constant(quote! { crate::#first() })
+        }
+        // The set is only built once, by `phf_set!` in the crate, which depends on `phf`.
+        Method::Debug if options.mode == Some(Mode::Phf) => {
+            let value: proc_macro2::TokenStream = match real_data.parse() {
+                Ok(i) => i,
+                Err(_) => return broken(),
+            };
+            let set = match rebuild::phf_set(value) {
+                Ok(i) => i,
+                Err(e) => {
+                    return syn::Error::new_spanned(
+                        output_type,
+                        format!("{}. Please execute stage one again.", e),
+                    )
+                    .to_compile_error()
+                    .into()
+                }
+            };
+            quote! {
+                #(#attrs)*
+                #visibility #qualifiers fn #name(#parameters) -> &'static ::phf::Set<&'static str> {
+                    static VALUE: ::phf::Set<&'static str> = #set;
+                    &VALUE
+                }
+            }
+        }
+        // The compiler reads the file itself, so the value never goes through this macro.
+        Method::Debug if included => {
+            let path = constany_common::include_arg(
+                &std::env::current_dir()
+                    .unwrap_or_default()
+                    .join(constany_common::include_path(&name.to_string())),
+            );
+            constant(quote! { include!(#path) })
+        }
+        Method::Debug => {
+            // Lexing the value is much cheaper than letting `syn` build an AST for large literals.
+            let value: proc_macro2::TokenStream = match real_data.parse() {
+                Ok(i) => i,
+                Err(_) => return broken(),
+            };
+            if let Some((pointer, element_type)) = constany_common::owned_slice(output_type) {
+                let slice_type: syn::Type = syn::parse_quote! { [#element_type] };
+                let value = match rebuild::rebuild(&slice_type, value) {
+                    Ok(i) => i,
+                    Err(e) => {
+                        return syn::Error::new_spanned(
+                            output_type,
+                            format!("{}. Please execute stage one again.", e),
+                        )
+                        .to_compile_error()
+                        .into()
+                    }
+                };
+                let rebuilt = match pointer {
+                    SlicePointer::Box => quote! { #const_name.to_vec().into_boxed_slice() },
+                    SlicePointer::Rc => quote! { std::rc::Rc::from(#const_name) },
+                };
                 quote! {
-                    #generated
-                    #visibility const fn #name() #return_type {
-                        return #const_name
+                    #[allow(non_upper_case_globals, dead_code)]
+                    const #const_name: &[#element_type] = &#value;
+                    #(#attrs)*
+                    #visibility #qualifiers fn #name(#parameters) #return_type {
+                        #rebuilt
+                    }
+                }
+            } else if !constany_common::is_primitive_type(output_type)
+                && rebuild::is_rebuildable(output_type)
+            {
+                let rebuilt = match rebuild::rebuild(output_type, value) {
+                    Ok(i) => i,
+                    Err(e) => {
+                        return syn::Error::new_spanned(
+                            output_type,
+                            format!("{}. Please execute stage one again.", e),
+                        )
+                        .to_compile_error()
+                        .into()
+                    }
+                };
+                if rebuild::is_const(output_type) {
+                    constant(rebuilt)
+                } else {
+                    quote! {
+                        #(#attrs)*
+                        #visibility #qualifiers fn #name(#parameters) #return_type {
+                            #rebuilt
+                        }
                     }
                 }
             } else {
-                let generated = quote! {
-                    #visibility const fn #name() #return_type {
-                        return DONOTTOUCHME
+                let value = if constany_common::is_primitive_type(output_type) {
+                    rebuild::rebuild(output_type, value)
+                } else {
+                    rebuild::debug_pub(output_type, value)
+                };
+                let value = match value {
+                    Ok(i) => i,
+                    Err(e) => {
+                        return syn::Error::new_spanned(output_type, e)
+                            .to_compile_error()
+                            .into()
+                    }
+                };
+                constant(value)
+            }
+        }
+        Method::Memop => {
+            let bytes = match decode_hex(&real_data) {
+                Some(i) => i,
+                None => return broken(),
+            };
+            let value = proc_macro2::Literal::byte_string(&bytes);
+            if const_value {
+                quote! {
+                    #[allow(non_upper_case_globals, dead_code)]
+                    const #const_name: [u8; std::mem::size_of::<#output_type>()] = *#value;
+                    #[allow(unknown_lints, unnecessary_transmutes, clippy::useless_transmute)]
+                    #(#attrs)*
+                    #visibility #qualifiers fn #name(#parameters) #return_type {
+                        unsafe {
+                            std::mem::transmute::<[u8; std::mem::size_of::<#output_type>()], #output_type>(#const_name)
+                        }
                     }
                 }
-                .to_string();
-                let generated = generated.replace("DONOTTOUCHME", &real_data);
-                let generated: syn::ItemFn = syn::parse_str(&generated).unwrap();
+            } else {
                 quote! {
-                    #generated
+                    #[allow(unknown_lints, unnecessary_transmutes, clippy::useless_transmute)]
+                    #(#attrs)*
+                    #visibility #qualifiers fn #name(#parameters) #return_type {
+                        let constant_value = *#value;
+                        unsafe {
+                            std::mem::transmute::<[u8; std::mem::size_of::<#output_type>()], #output_type>(constant_value)
+                        }
+                    }
                 }
             }
         }
-        1 => {
-            let byte_count = real_data.matches(',').count() + 1;
-            let output_type_2 = match return_type {
-                syn::ReturnType::Default => unimplemented!(),
-                syn::ReturnType::Type(_, j) => j,
+        Method::Bytes if constany_common::is_byte_vec(output_type) => {
+            let bytes = match decode_hex(&real_data) {
+                Some(i) => i,
+                None => return broken(),
             };
-            let (advanced_generated, generated) = if const_value {
-                let const_name = quote::format_ident!("CONST_VALUE_OF_FN_{}", name);
-                (
-                    Some(quote! {
-                        const #const_name : [u8; THISISTHEBYTECOUNT] = THISISTHEVALUE;
-                    }),
-                    quote! {
-                        #visibility fn #name() #return_type {
-                            unsafe {
-                                std::mem::transmute::<[u8; THISISTHEBYTECOUNT], #output_type_2>(#const_name)
-                            }
-                        }
-                    },
+            let value = proc_macro2::Literal::byte_string(&bytes);
+            quote! {
+                #[allow(non_upper_case_globals, dead_code)]
+                const #const_name: &[u8] = #value;
+                #(#attrs)*
+                #visibility #qualifiers fn #name(#parameters) #return_type {
+                    #const_name.to_vec()
+                }
+            }
+        }
+        Method::Bytes => {
+            let bytes = match decode_hex(&real_data) {
+                Some(i) => i,
+                None => return broken(),
+            };
+            if bytes.iter().position(|i| *i == 0).map(|i| i + 1) != Some(bytes.len()) {
+                return syn::Error::new_spanned(
+                    output_type,
+                    "The recorded string does not end with its only nul byte. Please execute stage one again.",
                 )
-            } else {
-                (
-                    None,
-                    quote! {
-                        #visibility fn #name() #return_type {
-                            let constant_value = THISISTHEVALUE;
-                            unsafe {
-                                std::mem::transmute::<[u8; THISISTHEBYTECOUNT], #output_type_2>(constant_value)
-                            }
+                .to_compile_error()
+                .into();
+            }
+            let value = proc_macro2::Literal::byte_string(&bytes);
+            quote! {
+                #[allow(non_upper_case_globals, dead_code)]
+                const #const_name: &[u8] = #value;
+                #(#attrs)*
+                #visibility #qualifiers fn #name(#parameters) #return_type {
+                    std::ffi::CStr::from_bytes_with_nul(#const_name).unwrap()
+                }
+            }
+        }
+        // The generated function decodes the string itself, so the crate needs no dependency.
+        Method::Base64 => {
+            let bytes = match decode_hex(&real_data) {
+                Some(i) => i,
+                None => return broken(),
+            };
+            let value = proc_macro2::Literal::string(&encode_base64(&bytes));
+            let from_bytes = &options.from_bytes;
+            quote! {
+                #[allow(non_upper_case_globals, dead_code)]
+                const #const_name: &str = #value;
+                #(#attrs)*
+                #visibility #qualifiers fn #name(#parameters) #return_type {
+                    let mut bytes = Vec::with_capacity(#const_name.len() / 4 * 3);
+                    let mut block = 0u32;
+                    let mut bits = 0;
+                    for i in #const_name.bytes() {
+                        let digit = match i {
+                            b'A'..=b'Z' => i - b'A',
+                            b'a'..=b'z' => i - b'a' + 26,
+                            b'0'..=b'9' => i - b'0' + 52,
+                            b'+' => 62,
+                            b'/' => 63,
+                            _ => break,
+                        };
+                        block = (block << 6 | digit as u32) & 0xffff;
+                        bits += 6;
+                        if bits >= 8 {
+                            bits -= 8;
+                            bytes.push((block >> bits) as u8);
                         }
-                    },
-                )
+                    }
+                    #from_bytes(&bytes)
+                }
+            }
+        }
+        Method::Serde if options.array_elementwise => {
+            let element_type = match output_type {
+                syn::Type::Array(i) => &i.elem,
+                _ => unreachable!("checked by `check_options`"),
             };
-            let generated = generated.to_string();
-            let advanced_generated = if let Some(i) = advanced_generated {
-                let constructed = i
-                    .to_string()
-                    .replace("THISISTHEBYTECOUNT", &byte_count.to_string())
-                    .replace("THISISTHEVALUE", &real_data);
-                let generated: syn::Item = syn::parse_str(&constructed).unwrap();
-                quote! {
-                    #generated
+            let elements = real_data.lines().map(proc_macro2::Literal::string);
+            quote! {
+                #(#attrs)*
+                #visibility #qualifiers fn #name(#parameters) #return_type {
+                    [#(::serde_json::from_str::<#element_type>(#elements).expect("Unable to deserialize the hard-coded value")),*]
                 }
-            } else {
-                quote! {}
+            }
+        }
+        Method::Serde => {
+            let value = proc_macro2::Literal::string(&real_data);
+            let deserialized = quote! {
+                ::serde_json::from_str(#const_name).expect("Unable to deserialize the hard-coded value")
+            };
+            let body = match output_type {
+                syn::Type::Reference(i) if options.mode == Some(Mode::LazyJson) => {
+                    let value_type = &i.elem;
+                    quote! {
+                        static VALUE: std::sync::OnceLock<#value_type> = std::sync::OnceLock::new();
+                        VALUE.get_or_init(|| #deserialized)
+                    }
+                }
+                _ => deserialized,
             };
-            let generated = generated
-                .replace("THISISTHEBYTECOUNT", &byte_count.to_string())
-                .replace("THISISTHEVALUE", &real_data);
-            let generated: syn::ItemFn = syn::parse_str(&generated).unwrap();
             quote! {
-                #advanced_generated
-                #generated
+                #[allow(non_upper_case_globals, dead_code)]
+                const #const_name: &str = #value;
+                #(#attrs)*
+                #visibility #qualifiers fn #name(#parameters) #return_type {
+                    #body
+                }
             }
         }
-        _ => unimplemented!(),
     };
-    return constructed.into();
+    // The length is counted from the record, so that it can size arrays in a const context.
+    let constructed = if options.with_len {
+        let len = real_data
+            .parse()
+            .map_err(|e: proc_macro2::LexError| format!("{:?}", e))
+            .and_then(rebuild::len);
+        match len {
+            Ok(len) => quote! {
+                #constructed
+                #visibility const #len_name: usize = #len;
+            },
+            Err(e) => {
+                return syn::Error::new_spanned(
+                    output_type,
+                    format!("{}. Please execute stage one again.", e),
+                )
+                .to_compile_error()
+                .into()
+            }
+        }
+    } else {
+        constructed
+    };
+    let dependencies = constany_common::track_dependencies(&options);
+    // The golden file is compared again when it changes.
+    let golden = options
+        .golden
+        .iter()
+        .map(|i| constany_common::include_arg(&constany_common::manifest_path(i)));
+    let constructed = export(constructed);
+    let constructed = quote! {
+        #(const _: &[u8] = include_bytes!(#golden);)*
+        #constructed
+        #dependencies
+        #feature_check
+        #original
+    };
+    constructed.into()
 }
 #[proc_macro_attribute]
 pub fn main_fn(_: TokenStream, item: TokenStream) -> TokenStream {
     let item: syn::ItemFn = syn::parse(item).unwrap();
+    if let Err(e) = constany_common::check_single_stage(&item, "main_fn") {
+        return e.to_compile_error().into();
+    }
     let generated = quote! {
         #item
     };
     generated.into()
 }
+
+#[proc_macro]
+pub fn const_expr(item: TokenStream) -> TokenStream {
+    let code_hash = seahash::hash(item.to_string().as_bytes());
+    let item = proc_macro2::TokenStream::from(item);
+    let resource_error = |problem: &str| {
+        syn::Error::new_spanned(&item, problem)
+            .to_compile_error()
+            .into()
+    };
+    let broken = || resource_error("Broken resource file. Please execute stage one again.");
+    let data = match constany_common::read_resource(&constany_common::expr_resource_name(code_hash))
+    {
+        Ok(i) => i,
+        Err(e) => return resource_error(&e),
+    };
+    let real_data = match (
+        Method::from_byte(data[0]),
+        String::from_utf8(data[constany_common::HEADER_LEN..].to_vec()),
+    ) {
+        (Some(Method::Debug), Ok(i)) => i,
+        _ => return broken(),
+    };
+    // The record is the name of the type on the first line, and the `Debug` output (which never contains a line break) on the second.
+    let (type_name, real_data) = match real_data.split_once('\n') {
+        Some(i) => i,
+        None => return broken(),
+    };
+    let value: proc_macro2::TokenStream = match real_data.parse() {
+        Ok(i) => i,
+        Err(_) => return broken(),
+    };
+    // Without its type, an integer literal is inferred as `i32` and silently truncated.
+    let value = match syn::parse_str::<syn::Type>(type_name) {
+        Ok(i) if constany_common::is_primitive_type(&i) => {
+            let value = match rebuild::rebuild(&i, value) {
+                Ok(i) => i,
+                Err(e) => {
+                    return resource_error(&format!("{}. Please execute stage one again.", e))
+                }
+            };
+            quote! {
+                {
+                    let value: #i = #value;
+                    value
+                }
+            }
+        }
+        _ => value,
+    };
+    value.into()
+}
+
+#[proc_macro]
+pub fn assert_baked(item: TokenStream) -> TokenStream {
+    let call = syn::parse_macro_input!(item as syn::Expr);
+    let (function, arguments) = match &call {
+        syn::Expr::Call(i) => (&*i.func, i.args.iter().collect()),
+        _ => (&call, Vec::new()),
+    };
+    let name = match function {
+        syn::Expr::Path(i) if i.path.get_ident().is_some() => i.path.get_ident().unwrap(),
+        _ => {
+            return syn::Error::new_spanned(
+                call,
+                "Expected the name of a constant function, or a call to it",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+    let original = quote::format_ident!("_{}_original_fn", name.to_string());
+    let test = quote::format_ident!("_{}_baked_test", name.to_string());
+    let generated = quote! {
+        #[cfg(test)]
+        #[test]
+        fn #test() {
+            #[allow(unused_unsafe)]
+            let (baked, original) = unsafe { (#name(#(#arguments),*), #original(#(#arguments),*)) };
+            assert_eq!(
+                baked,
+                original,
+                "The hard-coded value of `{}` differs from the value of the original function. It may read a file or an environment variable missing from `depends_on` or `depends_env`. Please execute stage one again",
+                stringify!(#name)
+            );
+        }
+    };
+    generated.into()
+}
@@ -0,0 +1,3 @@
+#!/bin/sh
+# The documentation site shows the readme, so it is generated from it instead of being edited by hand.
+cd "$(dirname "$0")/.." && cp readme.md docs/index.md